@@ -1,3 +1,5 @@
+use aoc::day16::plan::{Agent, Optimizer, Strategy};
+use aoc::day16::Valve;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 fn benchmark_aoc(c: &mut Criterion) {
@@ -29,9 +31,30 @@ fn benchmark_aoc(c: &mut Criterion) {
     c.bench_function("day01", |b| b.iter(|| black_box(aoc::solve(1))));
 }
 
+fn benchmark_day16_strategies(c: &mut Criterion) {
+    let input = aoc_lib::io::read_input(16).unwrap();
+    let network = aoc::day16::parse_input(&input);
+    let agents = vec![Agent::new(Valve::from("AA"), 26); 2];
+    let mut group = c.benchmark_group("day16 part 2");
+    for (name, strategy) in [
+        ("recursive", Strategy::Recursive),
+        ("subsets", Strategy::Subsets),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                Optimizer::new(&network, agents.clone())
+                    .with_strategy(strategy)
+                    .optimize()
+                    .pressure
+            })
+        });
+    }
+    group.finish();
+}
+
 criterion_group!(
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = benchmark_aoc
+    targets = benchmark_aoc, benchmark_day16_strategies
 );
 criterion_main!(benches);
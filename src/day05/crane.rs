@@ -0,0 +1,171 @@
+use std::fmt;
+
+use super::stacks::{Move, Stacks};
+
+/// A crane model decides how many crates are lifted at once.
+pub trait Crane {
+    /// Maximum number of crates the crane can lift at once.
+    fn capacity(&self) -> usize;
+
+    /// Sizes of the individual lifts needed to move `count` crates.
+    fn lifts(&self, count: usize) -> Lifts {
+        Lifts {
+            remaining: count,
+            capacity: self.capacity().max(1),
+        }
+    }
+}
+
+/// Moves one crate at a time (part 1).
+#[derive(Debug, Clone, Copy)]
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn capacity(&self) -> usize {
+        1
+    }
+}
+
+/// Moves all crates of a step at once (part 2).
+#[derive(Debug, Clone, Copy)]
+pub struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn capacity(&self) -> usize {
+        usize::MAX
+    }
+}
+
+/// Moves at most `k` crates at once.
+#[derive(Debug, Clone, Copy)]
+pub struct Capacity(pub usize);
+
+impl Crane for Capacity {
+    fn capacity(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Lifts {
+    remaining: usize,
+    capacity: usize,
+}
+
+impl Iterator for Lifts {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let n = self.remaining.min(self.capacity);
+        self.remaining -= n;
+        Some(n)
+    }
+}
+
+/// A single lift of the crane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// Index of the move this lift belongs to.
+    pub step: usize,
+    pub from: usize,
+    pub to: usize,
+    /// The lifted crates, from bottom to top.
+    pub crates: Vec<char>,
+}
+
+impl Event {
+    pub fn apply(&self, stacks: &mut Stacks) {
+        stacks.transfer(self.from, self.to, self.crates.len());
+    }
+
+    pub fn revert(&self, stacks: &mut Stacks) {
+        stacks.transfer(self.to, self.from, self.crates.len());
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}: lift ", self.step + 1)?;
+        for c in &self.crates {
+            write!(f, "[{c}]")?;
+        }
+        write!(f, " from {} to {}", self.from + 1, self.to + 1)
+    }
+}
+
+/// Records every lift of a crane so that the rearrangement can be replayed and reversed.
+#[derive(Debug, Clone)]
+pub struct MoveLog {
+    initial: Stacks,
+    events: Vec<Event>,
+}
+
+impl MoveLog {
+    pub fn record<C: Crane + ?Sized>(crane: &C, initial: &Stacks, moves: &[Move]) -> Self {
+        let mut stacks = initial.clone();
+        let mut events = Vec::with_capacity(moves.len());
+        for (step, mv) in moves.iter().enumerate() {
+            for n in crane.lifts(mv.count) {
+                let src = stacks.stack(mv.from);
+                let crates = src[src.len().saturating_sub(n)..].to_vec();
+                stacks.transfer(mv.from, mv.to, n);
+                events.push(Event {
+                    step,
+                    from: mv.from,
+                    to: mv.to,
+                    crates,
+                });
+            }
+        }
+        Self {
+            initial: initial.clone(),
+            events,
+        }
+    }
+
+    pub fn initial(&self) -> &Stacks {
+        &self.initial
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Stacks after the first `n` lifts.
+    pub fn replay(&self, n: usize) -> Stacks {
+        let mut stacks = self.initial.clone();
+        for event in &self.events[..n] {
+            event.apply(&mut stacks);
+        }
+        stacks
+    }
+
+    /// Stacks after all lifts.
+    pub fn result(&self) -> Stacks {
+        self.replay(self.events.len())
+    }
+
+    /// Undo the last `n` lifts, starting from the final arrangement.
+    pub fn rewind(&self, n: usize) -> Stacks {
+        let mut stacks = self.result();
+        for event in self.events.iter().rev().take(n) {
+            event.revert(&mut stacks);
+        }
+        stacks
+    }
+
+    /// Index of the first lift after which the top crates are `tops`.
+    pub fn find_tops(&self, tops: &str) -> Option<usize> {
+        let mut stacks = self.initial.clone();
+        for (i, event) in self.events.iter().enumerate() {
+            event.apply(&mut stacks);
+            if stacks.tops() == tops {
+                return Some(i + 1);
+            }
+        }
+        None
+    }
+}
@@ -1,82 +1,26 @@
-use std::collections::{HashMap, VecDeque};
+pub mod crane;
+pub mod planner;
+pub mod stacks;
 
 use log::debug;
 
-use aoc_lib::parse;
+use crane::{CrateMover9000, CrateMover9001};
+use stacks::{parse_moves, Stacks};
 
 pub fn solve(input: &[u8]) -> (String, String) {
-    let mut input = input;
-
-    let mut stacks_tmp: HashMap<usize, VecDeque<char>> = HashMap::with_capacity(10);
-    while !input.is_empty() {
-        for (i, &b) in input.iter().enumerate() {
-            if b == b'\n' {
-                input = &input[i..];
-                break;
-            }
-            if b.is_ascii_uppercase() {
-                let entry = stacks_tmp.entry(i);
-                entry
-                    .or_insert_with(|| VecDeque::with_capacity(10))
-                    .push_front(b as char);
-            }
-        }
-        input = parse::seek_next_line(input);
-        if input[0] == b'm' {
-            // line starts with 'move'
-            break;
-        }
-    }
-    let mut stacks: Vec<VecDeque<char>> = Vec::with_capacity(stacks_tmp.len());
-    let mut keys: Vec<&usize> = stacks_tmp.keys().collect();
-    keys.sort();
-    for k in keys {
-        stacks.push(stacks_tmp[k].clone()); // TODO: avoid clone
-    }
-    debug!("stacks: {:?}", stacks);
-    let mut stacks2 = stacks.clone();
-
-    // parse movements
-    while !input.is_empty() {
-        let (rest, count) = parse::positive(input, true).unwrap();
-        let (rest, from) = parse::positive(rest, true).unwrap();
-        let (rest, to) = parse::positive(rest, true).unwrap();
-        debug!("move {count} from {from} to {to}");
-        let from = (from - 1) as usize;
-        let to = (to - 1) as usize;
-        let mut items_for_stack2: VecDeque<char> = VecDeque::with_capacity(count as usize);
-        for _i in 0..count {
-            {
-                let item = stacks.get_mut(from).unwrap().pop_back().unwrap();
-                stacks.get_mut(to).unwrap().push_back(item);
-            }
-            {
-                let item = stacks2.get_mut(from).unwrap().pop_back().unwrap();
-                items_for_stack2.push_front(item);
-            }
-        }
-        while let Some(item) = items_for_stack2.pop_front() {
-            stacks2.get_mut(to).unwrap().push_back(item);
-        }
-
-        input = parse::seek_next_line(rest);
-    }
-
-    let mut part1_helper: Vec<u8> = Vec::with_capacity(stacks.len());
-    for deque in stacks {
-        let c = deque.back().unwrap();
-        part1_helper.push(*c as u8);
-    }
-    let part1 = String::from_utf8_lossy(&part1_helper);
+    let (stacks, rest) = Stacks::parse(input);
+    debug!("stacks:\n{stacks}");
+    let moves = parse_moves(rest);
 
-    let mut part2_helper: Vec<u8> = Vec::with_capacity(stacks2.len());
-    for deque in stacks2 {
-        let c = deque.back().unwrap();
-        part2_helper.push(*c as u8);
+    let mut stacks1 = stacks.clone();
+    let mut stacks2 = stacks;
+    for mv in &moves {
+        debug!("{mv}");
+        stacks1.apply(&CrateMover9000, mv);
+        stacks2.apply(&CrateMover9001, mv);
     }
-    let part2 = String::from_utf8_lossy(&part2_helper);
 
-    (part1.to_string(), part2.to_string())
+    (stacks1.tops(), stacks2.tops())
 }
 
 #[cfg(test)]
@@ -85,9 +29,10 @@ mod tests {
 
     const DAY: i32 = 05;
 
-    #[test]
-    fn example() {
-        let input = b"    [D]    
+    use crane::{Capacity, Crane, MoveLog};
+    use planner::{plan, render_input, PlanError};
+
+    const EXAMPLE: &[u8] = b"    [D]    
 [N] [C]    
 [Z] [M] [P]
  1   2   3
@@ -97,11 +42,105 @@ move 3 from 1 to 3
 move 2 from 2 to 1
 move 1 from 1 to 2
 ";
-        let solution = solve(&input[..]);
+
+    #[test]
+    fn example() {
+        let solution = solve(EXAMPLE);
         assert_eq!("CMZ", solution.0);
         assert_eq!("MCD", solution.1);
     }
 
+    #[test]
+    fn custom_capacity() {
+        let (stacks, rest) = Stacks::parse(EXAMPLE);
+        let moves = parse_moves(rest);
+        let mut stacks1 = stacks.clone();
+        let mut stacks2 = stacks;
+        for mv in &moves {
+            stacks1.apply(&Capacity(1), mv);
+            stacks2.apply(&Capacity(2), mv);
+        }
+        assert_eq!("CMZ", stacks1.tops());
+        assert_eq!("MCZ", stacks2.tops());
+    }
+
+    #[test]
+    fn move_log() {
+        let (stacks, rest) = Stacks::parse(EXAMPLE);
+        let moves = parse_moves(rest);
+        let log = MoveLog::record(&Capacity(2), &stacks, &moves);
+        // the move of 3 crates is split into two lifts
+        assert_eq!(5, log.events().len());
+        assert_eq!(vec!['N', 'D'], log.events()[1].crates);
+        assert_eq!("MCZ", log.result().tops());
+        assert_eq!(stacks, log.rewind(log.events().len()));
+        assert_eq!(log.replay(2), log.rewind(3));
+        assert_eq!(Some(2), log.find_tops("ZCD"));
+
+        let rendered = stacks.to_string();
+        assert_eq!(
+            "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n",
+            rendered
+        );
+        assert_eq!(stacks, Stacks::parse(rendered.as_bytes()).0);
+        assert_eq!("move 3 from 1 to 3", moves[1].to_string());
+    }
+
+    #[test]
+    fn planner() {
+        let (initial, rest) = Stacks::parse(EXAMPLE);
+        let moves = parse_moves(rest);
+        for (crane, expected) in [
+            (&CrateMover9000 as &dyn Crane, "CMZ"),
+            (&CrateMover9001, "MCD"),
+        ] {
+            let mut target = initial.clone();
+            for mv in &moves {
+                target.apply(crane, mv);
+            }
+            let planned = plan(crane, &initial, &target, 100_000).unwrap();
+            assert!(planned.len() <= moves.len());
+            let mut actual = initial.clone();
+            for mv in &planned {
+                actual.apply(crane, mv);
+            }
+            assert_eq!(target, actual);
+
+            let generated = render_input(&initial, &planned);
+            let solution = solve(generated.as_bytes());
+            let tops = if expected == "CMZ" {
+                solution.0
+            } else {
+                solution.1
+            };
+            assert_eq!(expected, tops);
+        }
+
+        let other = Stacks::new(vec![vec!['Z'], vec!['N'], vec![]]);
+        assert_eq!(
+            Err(PlanError::Crates),
+            plan(&CrateMover9000, &initial, &other, 100_000)
+        );
+        // moving single crates between two stacks cannot change their combined order
+        let two = Stacks::new(vec![vec!['A', 'B'], vec![]]);
+        let swapped = Stacks::new(vec![vec!['B', 'A'], vec![]]);
+        assert_eq!(
+            Err(PlanError::Unreachable),
+            plan(&CrateMover9000, &two, &swapped, 100_000)
+        );
+        assert_eq!(
+            1,
+            plan(
+                &CrateMover9001,
+                &two,
+                &Stacks::new(vec![vec![], vec!['A', 'B']]),
+                100_000
+            )
+            .unwrap()
+            .len()
+        );
+    }
+
     #[test]
     fn part1_and_part2() {
         let answer = solve(&aoc_lib::io::read_input(DAY).unwrap());
@@ -0,0 +1,152 @@
+use std::fmt;
+
+use ahash::AHashMap;
+use binary_heap_plus::{BinaryHeap, MinComparator};
+use log::{debug, trace};
+
+use super::crane::Crane;
+use super::stacks::{Move, Stacks};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanError {
+    /// Initial and target arrangement have a different number of stacks.
+    StackCount(usize, usize),
+    /// Initial and target arrangement do not consist of the same crates.
+    Crates,
+    /// The target cannot be reached with the given crane.
+    Unreachable,
+    /// The search visited more than the allowed number of arrangements.
+    Limit(usize),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::StackCount(a, b) => {
+                write!(f, "initial arrangement has {a} stacks but target has {b}")
+            }
+            PlanError::Crates => write!(f, "initial and target arrangement hold different crates"),
+            PlanError::Unreachable => write!(f, "target arrangement is unreachable"),
+            PlanError::Limit(n) => write!(f, "gave up after exploring {n} arrangements"),
+        }
+    }
+}
+
+// Every stack which is not a prefix of its target must be lifted from at least once, and every
+// stack which is missing crates must receive at least once. A single move does one of each.
+fn lower_bound(current: &Stacks, target: &Stacks) -> usize {
+    let mut lifts = 0;
+    let mut drops = 0;
+    for i in 0..current.len() {
+        let (have, want) = (current.stack(i), target.stack(i));
+        if !want.starts_with(have) {
+            lifts += 1;
+        } else if have.len() < want.len() {
+            drops += 1;
+        }
+    }
+    lifts.max(drops)
+}
+
+/// Search for a shortest list of moves which turns `initial` into `target`.
+///
+/// Based on A*; `max_states` bounds the number of explored arrangements.
+pub fn plan<C: Crane + ?Sized>(
+    crane: &C,
+    initial: &Stacks,
+    target: &Stacks,
+    max_states: usize,
+) -> Result<Vec<Move>, PlanError> {
+    if initial.len() != target.len() {
+        return Err(PlanError::StackCount(initial.len(), target.len()));
+    }
+    let crates = |stacks: &Stacks| {
+        let mut all: Vec<char> = (0..stacks.len())
+            .flat_map(|i| stacks.stack(i).iter().copied())
+            .collect();
+        all.sort_unstable();
+        all
+    };
+    if crates(initial) != crates(target) {
+        return Err(PlanError::Crates);
+    }
+
+    // arrangements are identified by their index into `states`
+    let mut states: Vec<Stacks> = vec![initial.clone()];
+    let mut index: AHashMap<Stacks, usize> = AHashMap::with_capacity(1024);
+    index.insert(initial.clone(), 0);
+    let mut g_score: Vec<usize> = vec![0];
+    let mut came_from: Vec<Option<(usize, Move)>> = vec![None];
+    let mut closed: Vec<bool> = vec![false];
+
+    let mut open: BinaryHeap<(usize, usize), MinComparator> = BinaryHeap::with_capacity_min(1024);
+    open.push((lower_bound(initial, target), 0));
+
+    let n = initial.len();
+    while let Some((_, current)) = open.pop() {
+        if closed[current] {
+            continue;
+        }
+        closed[current] = true;
+        if states[current] == *target {
+            let mut moves = Vec::with_capacity(g_score[current]);
+            let mut node = current;
+            while let Some((prev, mv)) = came_from[node] {
+                moves.push(mv);
+                node = prev;
+            }
+            moves.reverse();
+            debug!(
+                "found plan with {} moves after exploring {} arrangements",
+                moves.len(),
+                states.len()
+            );
+            return Ok(moves);
+        }
+
+        let tentative_g_score = g_score[current] + 1;
+        for from in 0..n {
+            for to in (0..n).filter(|&to| to != from) {
+                for count in 1..=states[current].stack(from).len() {
+                    let mv = Move::new(count, from, to);
+                    let mut neighbor = states[current].clone();
+                    neighbor.apply(crane, &mv);
+                    trace!("{mv}");
+                    let id = match index.get(&neighbor) {
+                        Some(&id) if tentative_g_score >= g_score[id] => continue,
+                        Some(&id) => id,
+                        None => {
+                            if states.len() >= max_states {
+                                return Err(PlanError::Limit(states.len()));
+                            }
+                            let id = states.len();
+                            index.insert(neighbor.clone(), id);
+                            states.push(neighbor);
+                            g_score.push(usize::MAX);
+                            came_from.push(None);
+                            closed.push(false);
+                            id
+                        }
+                    };
+                    g_score[id] = tentative_g_score;
+                    came_from[id] = Some((current, mv));
+                    let f_score = tentative_g_score + lower_bound(&states[id], target);
+                    open.push((f_score, id));
+                }
+            }
+        }
+    }
+
+    Err(PlanError::Unreachable)
+}
+
+/// Render a complete puzzle input, i.e. the drawing followed by the move list.
+pub fn render_input(initial: &Stacks, moves: &[Move]) -> String {
+    let mut s = initial.to_string();
+    s.push('\n');
+    for mv in moves {
+        s.push_str(&mv.to_string());
+        s.push('\n');
+    }
+    s
+}
@@ -0,0 +1,176 @@
+use std::fmt;
+
+use aoc_lib::parse;
+
+use super::crane::Crane;
+
+/// A single rearrangement step, e.g. `move 1 from 2 to 1`.
+///
+/// `from` and `to` are zero-based stack indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub count: usize,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl Move {
+    pub fn new(count: usize, from: usize, to: usize) -> Self {
+        Self { count, from, to }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.count,
+            self.from + 1,
+            self.to + 1
+        )
+    }
+}
+
+/// Parse the `move N from A to B` lines which follow the drawing.
+pub fn parse_moves(input: &[u8]) -> Vec<Move> {
+    let mut input = input;
+    let mut moves = Vec::with_capacity(512);
+    while !input.is_empty() {
+        if input[0] != b'm' {
+            input = parse::seek_next_line(input);
+            continue;
+        }
+        let (rest, count) = parse::positive(input, true).unwrap();
+        let (rest, from) = parse::positive(rest, true).unwrap();
+        let (rest, to) = parse::positive(rest, true).unwrap();
+        moves.push(Move::new(
+            count as usize,
+            (from - 1) as usize,
+            (to - 1) as usize,
+        ));
+        input = parse::seek_next_line(rest);
+    }
+    moves
+}
+
+/// Stacks of crates; the top of each stack is the last element.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Stacks(Vec<Vec<char>>);
+
+impl Stacks {
+    pub fn new(stacks: Vec<Vec<char>>) -> Self {
+        Self(stacks)
+    }
+
+    /// Parse the ASCII drawing at the beginning of `input`.
+    ///
+    /// Returns the stacks and the remaining input (i.e. the move list).
+    pub fn parse(input: &[u8]) -> (Self, &[u8]) {
+        let mut input = input;
+        let mut stacks: Vec<Vec<char>> = Vec::with_capacity(10);
+        while !input.is_empty() && input[0] != b'm' {
+            let pos_eol = input
+                .iter()
+                .position(|&b| b == b'\n')
+                .unwrap_or(input.len());
+            let line = &input[..pos_eol];
+            if line.contains(&b'[') {
+                for (i, &b) in line.iter().enumerate() {
+                    if b.is_ascii_uppercase() {
+                        let idx = i / 4;
+                        if idx >= stacks.len() {
+                            stacks.resize(idx + 1, Vec::new());
+                        }
+                        stacks[idx].push(b as char);
+                    }
+                }
+            } else {
+                // label line; also covers stacks which start out empty
+                let labels = line
+                    .split(|b| b.is_ascii_whitespace())
+                    .filter(|s| !s.is_empty());
+                let n = labels.count();
+                if n > stacks.len() {
+                    stacks.resize(n, Vec::new());
+                }
+            }
+            input = parse::seek_next_line(&input[pos_eol..]);
+        }
+        // the drawing lists crates from top to bottom
+        for stack in stacks.iter_mut() {
+            stack.reverse();
+        }
+        (Self(stacks), input)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Crates of stack `idx`, from bottom to top.
+    pub fn stack(&self, idx: usize) -> &[char] {
+        &self.0[idx]
+    }
+
+    /// The crate on top of each stack; empty stacks are skipped.
+    pub fn tops(&self) -> String {
+        self.0.iter().filter_map(|stack| stack.last()).collect()
+    }
+
+    /// Carry out `mv` with the given crane model.
+    pub fn apply<C: Crane + ?Sized>(&mut self, crane: &C, mv: &Move) {
+        for n in crane.lifts(mv.count) {
+            self.transfer(mv.from, mv.to, n);
+        }
+    }
+
+    /// Lift the top `n` crates of `from` in one go and put them onto `to`, preserving their order.
+    pub fn transfer(&mut self, from: usize, to: usize, n: usize) {
+        let at = self.0[from]
+            .len()
+            .checked_sub(n)
+            .unwrap_or_else(|| panic!("stack {} has less than {n} crates", from + 1));
+        if from == to {
+            return;
+        }
+        let (src, dst) = if from < to {
+            let (lhs, rhs) = self.0.split_at_mut(to);
+            (&mut lhs[from], &mut rhs[0])
+        } else {
+            let (lhs, rhs) = self.0.split_at_mut(from);
+            (&mut rhs[0], &mut lhs[to])
+        };
+        dst.extend(src.drain(at..));
+    }
+}
+
+impl fmt::Display for Stacks {
+    /// Render the stacks in the same format as the puzzle input.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self.0.iter().map(|stack| stack.len()).max().unwrap_or(0);
+        for level in (0..height).rev() {
+            for (i, stack) in self.0.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                match stack.get(level) {
+                    Some(c) => write!(f, "[{c}]")?,
+                    None => write!(f, "   ")?,
+                }
+            }
+            writeln!(f)?;
+        }
+        for i in 0..self.0.len() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, " {} ", i + 1)?;
+        }
+        writeln!(f)
+    }
+}
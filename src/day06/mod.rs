@@ -1,3 +1,5 @@
+pub mod stream;
+
 use arrayvec::ArrayVec;
 
 pub fn solve(input: &[u8]) -> (String, String) {
@@ -48,6 +50,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn streaming() {
+        use std::io::Read;
+        use stream::{Marker, MarkerDetector};
+
+        let mut detector = MarkerDetector::new(&[4, 14]);
+        let mut markers = Vec::new();
+        // feed the stream in several chunks
+        let reader = (&b"mjqjpqmgbl"[..])
+            .chain(&b"jsphdztnvjf"[..])
+            .chain(&b"qwrcgsmlb"[..]);
+        let n = detector.scan(reader, |m| markers.push(m)).unwrap();
+        assert_eq!(30, n);
+
+        let first = |size| markers.iter().find(|m| m.size == size).unwrap().position;
+        assert_eq!(7, first(4));
+        assert_eq!(19, first(14));
+        // every marker is reported, not just the first one ("pqmg")
+        assert!(markers.contains(&Marker {
+            size: 4,
+            position: 8
+        }));
+        let all_4: Vec<u64> = markers
+            .iter()
+            .filter(|m| m.size == 4)
+            .map(|m| m.position)
+            .collect();
+        let expected: Vec<u64> = (4..=30)
+            .filter(|&end| helper(&b"mjqjpqmgbljsphdztnvjfqwrcgsmlb"[end as usize - 4..], 4) == 4)
+            .collect();
+        assert_eq!(expected, all_4);
+    }
+
     #[test]
     fn part1_and_part2() {
         let answer = solve(&aoc_lib::io::read_input(DAY).unwrap());
@@ -0,0 +1,114 @@
+use std::io::{self, Read};
+
+use log::trace;
+
+const CHUNK_SIZE: usize = 8192;
+
+/// The last `size` bytes ending at `position` (exclusive) are pairwise distinct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker {
+    pub size: usize,
+    /// Number of bytes consumed when the marker was detected.
+    pub position: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Window {
+    size: usize,
+    counts: [u32; 256],
+    distinct: usize,
+}
+
+/// Detects markers of several window sizes in a single pass.
+///
+/// Each window keeps rolling byte counts, so every byte costs O(1) per window size.
+#[derive(Debug, Clone)]
+pub struct MarkerDetector {
+    windows: Vec<Window>,
+    /// ring buffer holding the last `history.len()` bytes
+    history: Vec<u8>,
+    position: u64,
+}
+
+impl MarkerDetector {
+    pub fn new(sizes: &[usize]) -> Self {
+        assert!(
+            sizes.iter().all(|&n| n > 0),
+            "window sizes must be positive"
+        );
+        let windows = sizes
+            .iter()
+            .map(|&size| Window {
+                size,
+                counts: [0; 256],
+                distinct: 0,
+            })
+            .collect();
+        let capacity = sizes.iter().copied().max().unwrap_or(1);
+        Self {
+            windows,
+            history: vec![0; capacity],
+            position: 0,
+        }
+    }
+
+    /// Number of bytes consumed so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn push(&mut self, b: u8, mut on_marker: impl FnMut(Marker)) {
+        let cap = self.history.len() as u64;
+        for window in self.windows.iter_mut() {
+            let size = window.size as u64;
+            if self.position >= size {
+                // the byte leaving the window; it is still in the ring buffer since size <= cap
+                let old = self.history[((self.position - size) % cap) as usize];
+                let count = &mut window.counts[old as usize];
+                *count -= 1;
+                if *count == 0 {
+                    window.distinct -= 1;
+                }
+            }
+            let count = &mut window.counts[b as usize];
+            *count += 1;
+            if *count == 1 {
+                window.distinct += 1;
+            }
+            if window.distinct == window.size {
+                let marker = Marker {
+                    size: window.size,
+                    position: self.position + 1,
+                };
+                trace!("found {:?}", marker);
+                on_marker(marker);
+            }
+        }
+        self.history[(self.position % cap) as usize] = b;
+        self.position += 1;
+    }
+
+    pub fn feed(&mut self, chunk: &[u8], mut on_marker: impl FnMut(Marker)) {
+        for &b in chunk {
+            self.push(b, &mut on_marker);
+        }
+    }
+
+    /// Consume `reader` until EOF and return the number of bytes read.
+    pub fn scan<R: Read>(
+        &mut self,
+        mut reader: R,
+        mut on_marker: impl FnMut(Marker),
+    ) -> io::Result<u64> {
+        let mut buf = [0; CHUNK_SIZE];
+        let start = self.position;
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => return Ok(self.position - start),
+                Ok(n) => self.feed(&buf[..n], &mut on_marker),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use std::fmt;
+
+use log::{debug, trace};
+
+pub type NodeId = usize;
+
+/// name and size of an `ls` entry; directories have no size
+type Entry = (String, Option<u64>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    Dir {
+        children: Vec<NodeId>,
+        /// whether `ls` has been run in this directory
+        listed: bool,
+    },
+    File {
+        size: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub name: String,
+    pub parent: Option<NodeId>,
+    pub kind: NodeKind,
+}
+
+impl Node {
+    pub fn is_dir(&self) -> bool {
+        matches!(self.kind, NodeKind::Dir { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnknownCommand(String),
+    UnexpectedOutput(String),
+    /// `cd ..` in the root directory
+    NoParent,
+    /// `cd` into something which is not a directory, or which was not listed by `ls`
+    NoSuchDirectory(String),
+    /// a directory was listed twice with different contents
+    ConflictingListing(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptError {
+    /// one-based line number
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ErrorKind::UnknownCommand(cmd) => write!(f, "unknown command '{cmd}'"),
+            ErrorKind::UnexpectedOutput(s) => write!(f, "unexpected output '{s}'"),
+            ErrorKind::NoParent => write!(f, "root directory has no parent"),
+            ErrorKind::NoSuchDirectory(name) => write!(f, "no such directory '{name}'"),
+            ErrorKind::ConflictingListing(path) => {
+                write!(f, "listing of {path} differs from previous listing")
+            }
+        }
+    }
+}
+
+/// Arena-backed directory tree; the root directory is always node 0.
+///
+/// Nodes are only ever appended, so a child always has a larger id than its parent.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+pub const ROOT: NodeId = 0;
+
+impl FileSystem {
+    pub fn new() -> Self {
+        let mut nodes = Vec::with_capacity(512);
+        nodes.push(Node {
+            name: String::from("/"),
+            parent: None,
+            kind: NodeKind::Dir {
+                children: Vec::new(),
+                listed: false,
+            },
+        });
+        Self { nodes }
+    }
+
+    /// Build the tree by replaying a transcript of `cd` and `ls` commands.
+    pub fn from_transcript(input: &[u8]) -> Result<Self, TranscriptError> {
+        let mut fs = Self::new();
+        let mut cwd = ROOT;
+        // line and entries of the current `ls`, if any
+        let mut listing: Option<(usize, Vec<Entry>)> = None;
+        let listing_err = |line| move |kind| TranscriptError { line, kind };
+
+        let text = String::from_utf8_lossy(input);
+        for (i, line) in text.lines().enumerate() {
+            let err = |kind| TranscriptError { line: i + 1, kind };
+            if let Some(cmd) = line.strip_prefix("$ ") {
+                if let Some((line, entries)) = listing.take() {
+                    fs.add_listing(cwd, entries).map_err(listing_err(line))?;
+                }
+                trace!("{line}");
+                if cmd == "ls" {
+                    listing = Some((i + 1, Vec::new()));
+                } else if let Some(dest) = cmd.strip_prefix("cd ") {
+                    cwd = match dest {
+                        "/" => ROOT,
+                        ".." => fs.nodes[cwd]
+                            .parent
+                            .ok_or_else(|| err(ErrorKind::NoParent))?,
+                        _ => fs.enter(cwd, dest).map_err(err)?,
+                    };
+                    debug!("cwd: {}", fs.path(cwd));
+                } else {
+                    return Err(err(ErrorKind::UnknownCommand(cmd.to_string())));
+                }
+            } else if line.is_empty() {
+                continue;
+            } else {
+                let (_, entries) = listing
+                    .as_mut()
+                    .ok_or_else(|| err(ErrorKind::UnexpectedOutput(line.to_string())))?;
+                let entry = match line.split_once(' ') {
+                    Some(("dir", name)) => (name.to_string(), None),
+                    Some((size, name)) => match size.parse::<u64>() {
+                        Ok(size) => (name.to_string(), Some(size)),
+                        Err(_) => return Err(err(ErrorKind::UnexpectedOutput(line.to_string()))),
+                    },
+                    None => return Err(err(ErrorKind::UnexpectedOutput(line.to_string()))),
+                };
+                entries.push(entry);
+            }
+        }
+        if let Some((line, entries)) = listing.take() {
+            fs.add_listing(cwd, entries).map_err(listing_err(line))?;
+        }
+        Ok(fs)
+    }
+
+    fn add_node(&mut self, parent: NodeId, name: &str, size: Option<u64>) -> NodeId {
+        let id = self.nodes.len();
+        let kind = match size {
+            Some(size) => NodeKind::File { size },
+            None => NodeKind::Dir {
+                children: Vec::new(),
+                listed: false,
+            },
+        };
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: Some(parent),
+            kind,
+        });
+        if let NodeKind::Dir { children, .. } = &mut self.nodes[parent].kind {
+            children.push(id);
+        }
+        id
+    }
+
+    fn enter(&mut self, cwd: NodeId, name: &str) -> Result<NodeId, ErrorKind> {
+        match self.child(cwd, name) {
+            Some(id) if self.nodes[id].is_dir() => Ok(id),
+            Some(_) => Err(ErrorKind::NoSuchDirectory(name.to_string())),
+            None => match self.nodes[cwd].kind {
+                // we have not seen the contents of cwd yet, so trust the transcript
+                NodeKind::Dir { listed: false, .. } => Ok(self.add_node(cwd, name, None)),
+                _ => Err(ErrorKind::NoSuchDirectory(name.to_string())),
+            },
+        }
+    }
+
+    fn add_listing(&mut self, dir: NodeId, entries: Vec<Entry>) -> Result<(), ErrorKind> {
+        if matches!(self.nodes[dir].kind, NodeKind::Dir { listed: true, .. }) {
+            // compare the entries as a whole, so a duplicated name can't stand in for another
+            let mut previous: Vec<(&str, Option<u64>)> = self
+                .children(dir)
+                .iter()
+                .map(|&id| match self.nodes[id].kind {
+                    NodeKind::File { size } => (self.nodes[id].name.as_str(), Some(size)),
+                    NodeKind::Dir { .. } => (self.nodes[id].name.as_str(), None),
+                })
+                .collect();
+            let mut current: Vec<(&str, Option<u64>)> =
+                entries.iter().map(|(n, s)| (n.as_str(), *s)).collect();
+            previous.sort_unstable();
+            current.sort_unstable();
+            if previous != current {
+                return Err(ErrorKind::ConflictingListing(self.path(dir)));
+            }
+            return Ok(());
+        }
+        for (name, size) in entries {
+            match self.child(dir, &name) {
+                Some(id) => {
+                    let same = match (&self.nodes[id].kind, size) {
+                        (NodeKind::File { size: a }, Some(b)) => *a == b,
+                        (NodeKind::Dir { .. }, None) => true,
+                        _ => false,
+                    };
+                    if !same {
+                        return Err(ErrorKind::ConflictingListing(self.path(dir)));
+                    }
+                }
+                None => {
+                    self.add_node(dir, &name, size);
+                }
+            }
+        }
+        if let NodeKind::Dir { listed, .. } = &mut self.nodes[dir].kind {
+            *listed = true;
+        }
+        Ok(())
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        match &self.nodes[id].kind {
+            NodeKind::Dir { children, .. } => children,
+            NodeKind::File { .. } => &[],
+        }
+    }
+
+    pub fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children(id)
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].name == name)
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    /// Absolute path of a node, e.g. `/a/e`.
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names = Vec::with_capacity(16);
+        let mut current = id;
+        while let Some(parent) = self.nodes[current].parent {
+            names.push(self.nodes[current].name.as_str());
+            current = parent;
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    /// Resolve an absolute path.
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        path.split('/')
+            .filter(|s| !s.is_empty())
+            .try_fold(ROOT, |id, name| self.child(id, name))
+    }
+
+    /// Total size of every node, indexed by `NodeId` (like `du`).
+    pub fn du(&self) -> Vec<u64> {
+        let mut sizes: Vec<u64> = self
+            .nodes
+            .iter()
+            .map(|node| match node.kind {
+                NodeKind::File { size } => size,
+                NodeKind::Dir { .. } => 0,
+            })
+            .collect();
+        // children come after their parents, so a single backwards pass suffices
+        for id in (1..self.nodes.len()).rev() {
+            let parent = self.nodes[id].parent.unwrap();
+            sizes[parent] += sizes[id];
+        }
+        sizes
+    }
+
+    /// All directories together with their total size.
+    pub fn dirs(&self) -> impl Iterator<Item = (NodeId, u64)> + '_ {
+        let sizes = self.du();
+        (0..self.nodes.len())
+            .filter(|&id| self.nodes[id].is_dir())
+            .map(move |id| (id, sizes[id]))
+    }
+
+    /// Nodes below `start` (inclusive) matching `pred`, in depth-first order (like `find`).
+    ///
+    /// The predicate gets the node and its total size.
+    pub fn find<P>(&self, start: NodeId, pred: P) -> Vec<NodeId>
+    where
+        P: Fn(&Node, u64) -> bool,
+    {
+        let sizes = self.du();
+        let mut result = Vec::new();
+        let mut stack = vec![start];
+        while let Some(id) = stack.pop() {
+            if pred(&self.nodes[id], sizes[id]) {
+                result.push(id);
+            }
+            stack.extend(self.children(id).iter().rev());
+        }
+        result
+    }
+
+    /// Render the subtree at `start` like the puzzle description does (like `tree`).
+    pub fn tree(&self, start: NodeId) -> String {
+        let mut out = String::with_capacity(64 * self.nodes.len());
+        self.tree_helper(start, 0, &mut out);
+        out
+    }
+
+    fn tree_helper(&self, id: NodeId, level: usize, out: &mut String) {
+        use std::fmt::Write;
+
+        let node = &self.nodes[id];
+        for _i in 0..level {
+            out.push_str("  ");
+        }
+        match node.kind {
+            NodeKind::Dir { .. } => writeln!(out, "- {} (dir)", node.name).unwrap(),
+            NodeKind::File { size } => {
+                writeln!(out, "- {} (file, size={size})", node.name).unwrap()
+            }
+        }
+        let mut children = self.children(id).to_vec();
+        children.sort_by(|&a, &b| self.nodes[a].name.cmp(&self.nodes[b].name));
+        for child in children {
+            self.tree_helper(child, level + 1, out);
+        }
+    }
+}
+
+impl Default for FileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.tree(ROOT))
+    }
+}
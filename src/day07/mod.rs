@@ -1,151 +1,29 @@
-use ahash::AHashMap;
-use log::{debug, trace};
+pub mod fs;
+pub mod planner;
 
-use aoc_lib::parse;
+use log::debug;
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
-struct Directory {
-    path: String,
-}
-
-impl Directory {
-    pub fn new(start: &str) -> Directory {
-        let mut path = String::with_capacity(1024);
-        path.push_str(start);
-        Directory { path }
-    }
-
-    pub fn is_root(&self) -> bool {
-        self.path.len() == 1
-    }
-
-    pub fn go_to_root(&mut self) {
-        self.path.truncate(1);
-    }
-
-    pub fn parent(&mut self) {
-        debug!("going to parent of {}", self.path);
-        let mut pos = self.path.rfind('/').unwrap();
-        if pos == 0 {
-            pos = 1;
-        }
-        self.path.truncate(pos);
-    }
-
-    pub fn enter(&mut self, other: &str) {
-        debug!("Entering {other}");
-        if !self.is_root() {
-            self.path.push('/');
-        }
-        self.path.push_str(other);
-    }
-}
+use fs::FileSystem;
+use planner::{plan, DiskConfig, Objective, PlanOptions};
 
 pub fn solve(input: &[u8]) -> (String, String) {
-    let mut input = input;
-
-    let mut dirs: AHashMap<Directory, u64> = AHashMap::with_capacity(128);
-    let start = "/";
-    let mut cwd = Directory::new(start);
-
-    while !input.is_empty() {
-        debug!("=== parsing line ===");
-        if input[0] == b'$' {
-            // parse cmd
-            match input[2] {
-                b'c' => {
-                    // cd command
-                    let pos_eol = input
-                        .iter()
-                        .enumerate()
-                        .find(|(_i, &x)| x == b'\n')
-                        .unwrap()
-                        .0;
-                    let dest = &input[5..pos_eol];
-                    trace!("$ cd {}", String::from_utf8_lossy(dest));
-                    match dest {
-                        b".." => {
-                            cwd.parent();
-                        }
-                        b"/" => {
-                            cwd.go_to_root();
-                        }
-                        _ => {
-                            cwd.enter(&String::from_utf8_lossy(dest));
-                        }
-                    }
-
-                    input = &input[pos_eol..];
-                    input = parse::seek_next_line(input);
-                }
-                b'l' => {
-                    // ls command
-                    trace!("$ ls");
-                    input = parse::seek_next_line(input);
-                    // parse ls output
-                    while !input.is_empty() && input[0] != b'$' {
-                        match input[0] {
-                            b'd' => {
-                                // entry is a dir;
-                                // we don't do anything with that information though
-                                let pos_eol = input
-                                    .iter()
-                                    .enumerate()
-                                    .find(|(_i, &x)| x == b'\n')
-                                    .unwrap()
-                                    .0;
-                                let dir = &input[4..pos_eol];
-                                trace!("dir {}", String::from_utf8_lossy(dir));
-                            }
-                            b'0'..=b'9' => {
-                                // entry is a file
-                                let (rest, fsize) = parse::positive(input, false).unwrap();
-                                let pos_eol = rest
-                                    .iter()
-                                    .enumerate()
-                                    .find(|(_i, &x)| x == b'\n')
-                                    .unwrap()
-                                    .0;
-                                let fname = &rest[1..pos_eol];
-                                trace!("{fsize} {}", String::from_utf8_lossy(fname));
-
-                                {
-                                    //  update dir and parent dirs
-                                    let mut tmp = cwd.clone();
-                                    loop {
-                                        debug!("adding {fsize} to {:?}", tmp);
-                                        let existing = dirs.entry(tmp.clone()).or_insert(0);
-                                        *existing += fsize;
-                                        if tmp.is_root() {
-                                            break;
-                                        }
-                                        tmp.parent();
-                                    }
-                                }
-                                input = rest;
-                            }
-                            _ => panic!("unexpected ls entry"),
-                        }
-                        input = parse::seek_next_line(input);
-                    }
-                }
-                _ => panic!("unsupported command"),
-            }
-        }
-        debug!("cwd: {:?}", cwd);
-    }
-
-    let part1: u64 = dirs.values().filter(|&&x| x <= 100000).sum();
+    let fs = FileSystem::from_transcript(input).unwrap();
+    debug!("{fs}");
 
-    let total: u64 = 70000000;
-    let free: u64 = total
-        - dirs[&Directory {
-            path: String::from("/"),
-        }];
-    let update_size: u64 = 30000000;
-    let need_to_free = update_size - free;
+    let part1: u64 = fs
+        .dirs()
+        .map(|(_, size)| size)
+        .filter(|&x| x <= 100000)
+        .sum();
 
-    let part2 = dirs.values().filter(|&&x| x >= need_to_free).min().unwrap();
+    // smallest single directory which frees up enough space
+    let options = PlanOptions {
+        objective: Objective::MinBytes,
+        include_files: false,
+        max_deletions: 1,
+        ..Default::default()
+    };
+    let part2 = plan(&fs, &DiskConfig::default(), &options).unwrap().freed;
 
     (part1.to_string(), part2.to_string())
 }
@@ -156,9 +34,7 @@ mod tests {
 
     const DAY: i32 = 07;
 
-    #[test]
-    fn example() {
-        let input = b"$ cd /
+    const EXAMPLE: &[u8] = b"$ cd /
 $ ls
 dir a
 14848514 b.txt
@@ -183,8 +59,239 @@ $ ls
 7214296 k
 ";
 
-        let solution = solve(&input[..]);
+    #[test]
+    fn example() {
+        let solution = solve(EXAMPLE);
         assert_eq!("95437", solution.0);
+        assert_eq!("24933642", solution.1);
+    }
+
+    #[test]
+    fn filesystem_queries() {
+        use fs::ROOT;
+
+        let fs = FileSystem::from_transcript(EXAMPLE).unwrap();
+        let e = fs.lookup("/a/e").unwrap();
+        assert_eq!("/a/e", fs.path(e));
+        assert_eq!(fs.lookup("/a"), fs.parent(e));
+        assert_eq!(584, fs.du()[e]);
+        assert_eq!(48381165, fs.du()[ROOT]);
+
+        let small_dirs: Vec<String> = fs
+            .find(ROOT, |node, size| node.is_dir() && size <= 100000)
+            .into_iter()
+            .map(|id| fs.path(id))
+            .collect();
+        assert_eq!(vec!["/a", "/a/e"], small_dirs);
+
+        let expected = "- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+";
+        assert_eq!(expected, fs.to_string());
+    }
+
+    #[test]
+    fn deletion_planner() {
+        let fs = FileSystem::from_transcript(EXAMPLE).unwrap();
+
+        let config = DiskConfig::default();
+        let p = plan(&fs, &config, &PlanOptions::default()).unwrap();
+        assert_eq!(8381165, p.need_to_free);
+        assert_eq!(vec![(String::from("/c.dat"), 8504156)], p.explain(&fs));
+
+        let config = DiskConfig {
+            total: 70000000,
+            required: 40000000,
+        };
+        let p = plan(&fs, &config, &PlanOptions::default()).unwrap();
+        assert_eq!(18908688, p.freed);
+        let mut paths: Vec<String> = p.explain(&fs).into_iter().map(|x| x.0).collect();
+        paths.sort();
+        assert_eq!(vec!["/b.txt", "/d/j"], paths);
+
+        let options = PlanOptions {
+            objective: Objective::MinDeletions,
+            ..Default::default()
+        };
+        let p = plan(&fs, &config, &options).unwrap();
+        assert_eq!(vec![(String::from("/d"), 24933642)], p.explain(&fs));
+
+        let config = DiskConfig {
+            total: 70000000,
+            required: 100000000,
+        };
+        assert_eq!(None, plan(&fs, &config, &PlanOptions::default()));
+    }
+
+    /// `dirs` directories with `subdirs` subdirectories each, `files` files in every one of them
+    fn generated_tree(dirs: usize, subdirs: usize, files: usize) -> FileSystem {
+        let mut seed: u64 = 7;
+        let mut size = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % 2_000_000 + 1
+        };
+        let mut transcript = String::from("$ cd /\n$ ls\n");
+        for d in 0..dirs {
+            transcript.push_str(&format!("dir d{d}\n"));
+        }
+        for d in 0..dirs {
+            transcript.push_str(&format!("$ cd d{d}\n$ ls\n"));
+            for s in 0..subdirs {
+                transcript.push_str(&format!("dir s{s}\n"));
+            }
+            for f in 0..files {
+                transcript.push_str(&format!("{} f{f}\n", size()));
+            }
+            for s in 0..subdirs {
+                transcript.push_str(&format!("$ cd s{s}\n$ ls\n"));
+                for f in 0..files {
+                    transcript.push_str(&format!("{} f{f}\n", size()));
+                }
+                transcript.push_str("$ cd ..\n");
+            }
+            transcript.push_str("$ cd ..\n");
+        }
+        FileSystem::from_transcript(transcript.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn large_tree() {
+        let fs = generated_tree(20, 3, 10);
+        let sizes = fs.du();
+        assert_eq!(1 + 20 * 4 + 20 * 4 * 10, sizes.len());
+
+        // a full disk; more than the biggest file, less than a top-level directory
+        let config = DiskConfig {
+            total: sizes[0],
+            required: sizes[0] / 40,
+        };
+        let thinned = PlanOptions {
+            max_states: 128,
+            ..Default::default()
+        };
+        let p = plan(&fs, &config, &thinned).unwrap();
+        assert!(!p.exact);
+        assert!(p.freed >= p.need_to_free);
+        assert_eq!(
+            p.freed,
+            p.deletions.iter().map(|&id| sizes[id]).sum::<u64>()
+        );
+        // no deletion is nested in another one
+        for &id in &p.deletions {
+            let mut current = fs.parent(id);
+            while let Some(parent) = current {
+                assert!(!p.deletions.contains(&parent));
+                current = fs.parent(parent);
+            }
+        }
+        // at least as good as deleting a single directory
+        let single = PlanOptions {
+            include_files: false,
+            max_deletions: 1,
+            ..Default::default()
+        };
+        assert!(p.freed <= plan(&fs, &config, &single).unwrap().freed);
+    }
+
+    #[test]
+    fn planner_matches_brute_force() {
+        use fs::{NodeId, ROOT};
+
+        /// (freed, deletions) of every selection of non-nested entries below `id`
+        fn selections(fs: &FileSystem, id: NodeId, sizes: &[u64]) -> Vec<(u64, usize)> {
+            let mut result = vec![(0, 0)];
+            for &child in fs.children(id) {
+                let child_selections = selections(fs, child, sizes);
+                result = result
+                    .iter()
+                    .flat_map(|&(f, n)| child_selections.iter().map(move |&(g, m)| (f + g, n + m)))
+                    .collect();
+            }
+            if id != ROOT {
+                result.push((sizes[id], 1));
+            }
+            result
+        }
+
+        let fs = generated_tree(4, 0, 4);
+        let sizes = fs.du();
+        let all = selections(&fs, ROOT, &sizes);
+        assert_eq!(17 * 17 * 17 * 17, all.len());
+        let config = DiskConfig {
+            total: sizes[0],
+            required: sizes[0] / 3,
+        };
+        let need = config.need_to_free(sizes[0]);
+        let complete = all.iter().filter(|&&(freed, _)| freed >= need);
+
+        let p = plan(&fs, &config, &PlanOptions::default()).unwrap();
+        assert!(p.exact);
+        assert_eq!(
+            complete.clone().map(|&(freed, _)| freed).min(),
+            Some(p.freed)
+        );
+
+        let options = PlanOptions {
+            objective: Objective::MinDeletions,
+            ..Default::default()
+        };
+        let p = plan(&fs, &config, &options).unwrap();
+        assert!(p.exact);
+        let best = complete.map(|&(freed, n)| (n, freed)).min().unwrap();
+        assert_eq!(best, (p.deletions.len(), p.freed));
+
+        // more than 128 partial selections somewhere
+        let thinned = PlanOptions {
+            max_states: 128,
+            ..Default::default()
+        };
+        assert!(!plan(&fs, &config, &thinned).unwrap().exact);
+    }
+
+    #[test]
+    fn inconsistent_transcripts() {
+        // same listing twice is fine
+        let input = b"$ cd /\n$ ls\ndir a\n1 b\n$ ls\n1 b\ndir a\n";
+        assert!(FileSystem::from_transcript(input).is_ok());
+
+        let input = b"$ cd /\n$ ls\ndir a\n1 b\n$ cd a\n$ cd ..\n$ ls\ndir a\n2 b\n";
+        let err = FileSystem::from_transcript(input).unwrap_err();
+        assert_eq!(7, err.line);
+        assert_eq!(
+            fs::ErrorKind::ConflictingListing(String::from("/")),
+            err.kind
+        );
+
+        // same number of entries, but one of them twice
+        let input = b"$ cd /\n$ ls\ndir a\n1 b\n$ ls\n1 b\n1 b\n$ cd a\n";
+        let err = FileSystem::from_transcript(input).unwrap_err();
+        assert_eq!(5, err.line);
+        assert_eq!(
+            fs::ErrorKind::ConflictingListing(String::from("/")),
+            err.kind
+        );
+
+        let input = b"$ cd /\n$ ls\ndir a\n$ cd b\n";
+        let err = FileSystem::from_transcript(input).unwrap_err();
+        assert_eq!(4, err.line);
+        assert_eq!(fs::ErrorKind::NoSuchDirectory(String::from("b")), err.kind);
+
+        let err = FileSystem::from_transcript(b"$ cd ..\n").unwrap_err();
+        assert_eq!("line 1: root directory has no parent", err.to_string());
     }
 
     #[test]
@@ -0,0 +1,241 @@
+use std::rc::Rc;
+
+use log::debug;
+
+use super::fs::{FileSystem, NodeId, NodeKind, ROOT};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskConfig {
+    pub total: u64,
+    /// free space required by the update
+    pub required: u64,
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            total: 70000000,
+            required: 30000000,
+        }
+    }
+}
+
+impl DiskConfig {
+    /// Number of bytes which must be deleted so that the update fits.
+    pub fn need_to_free(&self, used: u64) -> u64 {
+        let free = self.total.saturating_sub(used);
+        self.required.saturating_sub(free)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    /// Delete as few bytes as possible (ties: fewer deletions).
+    MinBytes,
+    /// Delete as few entries as possible (ties: fewer bytes).
+    MinDeletions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlanOptions {
+    pub objective: Objective,
+    /// whether single files may be deleted, or only whole directories
+    pub include_files: bool,
+    pub max_deletions: usize,
+    /// Partial selections kept per subtree. Beyond that, an evenly spread subset of them is
+    /// kept, which bounds the running time on large trees but makes the plan approximate.
+    pub max_states: usize,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            objective: Objective::MinBytes,
+            include_files: true,
+            max_deletions: usize::MAX,
+            max_states: usize::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    /// Deleted nodes; none of them is nested in another one.
+    pub deletions: Vec<NodeId>,
+    pub freed: u64,
+    pub need_to_free: u64,
+    /// whether the plan is optimal, i.e. no partial selections were dropped for `max_states`
+    pub exact: bool,
+}
+
+impl Plan {
+    /// Paths to delete, together with their size.
+    pub fn explain(&self, fs: &FileSystem) -> Vec<(String, u64)> {
+        let sizes = fs.du();
+        self.deletions
+            .iter()
+            .map(|&id| (fs.path(id), sizes[id]))
+            .collect()
+    }
+}
+
+/// Deleted nodes of a selection; shared between selections so that combining them is cheap.
+#[derive(Debug)]
+enum Deletions {
+    None,
+    One(NodeId),
+    Both(Rc<Deletions>, Rc<Deletions>),
+}
+
+impl Deletions {
+    fn collect(&self, out: &mut Vec<NodeId>) {
+        match self {
+            Deletions::None => {}
+            Deletions::One(id) => out.push(*id),
+            Deletions::Both(lhs, rhs) => {
+                lhs.collect(out);
+                rhs.collect(out);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    freed: u64,
+    count: usize,
+    deletions: Rc<Deletions>,
+}
+
+/// Find the best set of non-nested entries whose deletion frees enough space for the update.
+///
+/// Works bottom-up on the tree and keeps, for every subtree, only those partial selections
+/// which can still be part of an optimal plan. The plan is optimal unless some subtree has more
+/// than `options.max_states` of them, see [`Plan::exact`].
+pub fn plan(fs: &FileSystem, config: &DiskConfig, options: &PlanOptions) -> Option<Plan> {
+    let sizes = fs.du();
+    let need_to_free = config.need_to_free(sizes[ROOT]);
+    debug!("need to free {need_to_free} bytes");
+
+    let mut exact = true;
+    let best = states(fs, ROOT, &sizes, need_to_free, options, &mut exact)
+        .into_iter()
+        .find(|state| state.freed >= need_to_free)?;
+    let mut deletions = Vec::with_capacity(best.count);
+    best.deletions.collect(&mut deletions);
+    Some(Plan {
+        deletions,
+        freed: best.freed,
+        need_to_free,
+        exact,
+    })
+}
+
+fn states(
+    fs: &FileSystem,
+    id: NodeId,
+    sizes: &[u64],
+    need: u64,
+    options: &PlanOptions,
+    exact: &mut bool,
+) -> Vec<State> {
+    let mut result = vec![State {
+        freed: 0,
+        count: 0,
+        deletions: Rc::new(Deletions::None),
+    }];
+    for &child in fs.children(id) {
+        let child_states = states(fs, child, sizes, need, options, exact);
+        let mut combined = Vec::with_capacity(result.len() * child_states.len());
+        for lhs in &result {
+            for rhs in &child_states {
+                if lhs.count + rhs.count > options.max_deletions {
+                    continue;
+                }
+                let deletions = match (&*lhs.deletions, &*rhs.deletions) {
+                    (Deletions::None, _) => rhs.deletions.clone(),
+                    (_, Deletions::None) => lhs.deletions.clone(),
+                    _ => Rc::new(Deletions::Both(
+                        lhs.deletions.clone(),
+                        rhs.deletions.clone(),
+                    )),
+                };
+                combined.push(State {
+                    freed: lhs.freed + rhs.freed,
+                    count: lhs.count + rhs.count,
+                    deletions,
+                });
+            }
+        }
+        result = prune(combined, need, options, exact);
+    }
+
+    let deletable = match fs.node(id).kind {
+        NodeKind::Dir { .. } => id != ROOT,
+        NodeKind::File { .. } => options.include_files,
+    };
+    if deletable && options.max_deletions > 0 {
+        // deleting this node supersedes any selection inside of it
+        result.push(State {
+            freed: sizes[id],
+            count: 1,
+            deletions: Rc::new(Deletions::One(id)),
+        });
+        result = prune(result, need, options, exact);
+    }
+    result
+}
+
+/// Drop selections which cannot lead to a better plan; the best complete plan (if any) comes first.
+///
+/// Clears `exact` if selections had to be thinned out.
+fn prune(mut states: Vec<State>, need: u64, options: &PlanOptions, exact: &mut bool) -> Vec<State> {
+    let key = |s: &State| match options.objective {
+        Objective::MinBytes => (s.freed, s.count as u64),
+        Objective::MinDeletions => (s.count as u64, s.freed),
+    };
+    // adding further deletions to a complete selection never improves it
+    let best_complete = states
+        .iter()
+        .filter(|s| s.freed >= need)
+        .min_by_key(|s| key(s))
+        .cloned();
+    states.retain(|s| s.freed < need);
+
+    // same amount of bytes: fewer deletions are better for either objective
+    states.sort_unstable_by_key(|s| (s.freed, s.count));
+    states.dedup_by_key(|s| s.freed);
+    if states.len() > options.max_states {
+        thin_out(&mut states, options.max_states.max(1));
+        *exact = false;
+    }
+
+    if let Some(best) = best_complete {
+        states.insert(0, best);
+    }
+    states
+}
+
+/// Keep at most `max` states, evenly spread over the sorted ones; the last one frees the most
+/// bytes and is always kept.
+fn thin_out(states: &mut Vec<State>, max: usize) {
+    let n = states.len();
+    if n <= max {
+        return;
+    }
+    debug!("thinning out {n} states");
+    let keep: Vec<usize> = match max {
+        1 => vec![n - 1],
+        _ => (0..max).map(|k| k * (n - 1) / (max - 1)).collect(),
+    };
+    let mut kept = keep.iter().peekable();
+    let mut i = 0;
+    states.retain(|_| {
+        let hit = kept.peek() == Some(&&i);
+        if hit {
+            kept.next();
+        }
+        i += 1;
+        hit
+    });
+}
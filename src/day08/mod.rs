@@ -1,137 +1,168 @@
-use ahash::AHashSet;
+use std::io::{self, Write};
 
 use aoc_lib::parse;
 
-const MAX_ROWS: usize = 99;
-const MAX_COLS: usize = 99;
+pub const NORTH: u8 = 1;
+pub const EAST: u8 = 2;
+pub const SOUTH: u8 = 4;
+pub const WEST: u8 = 8;
 
-pub fn solve(input: &[u8]) -> (String, String) {
-    let mut grid: [[i8; MAX_COLS]; MAX_ROWS] = [[0; MAX_COLS]; MAX_ROWS];
-    let mut rows: u32 = 0;
-    let mut cols: u32 = 0;
-
-    let mut input = input;
-    while !input.is_empty() {
-        for (col, &b) in input.iter().enumerate() {
-            if b == b'\n' {
-                cols = col as u32;
-                rows += 1;
-                input = &input[col..];
-                break;
-            }
-            grid[rows as usize][col] = (b - b'0') as i8;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forest {
+    pub rows: usize,
+    pub cols: usize,
+    /// row-major
+    heights: Vec<u8>,
+}
+
+impl Forest {
+    pub fn parse(input: &[u8]) -> Self {
+        let cols = input
+            .iter()
+            .position(|&b| b == b'\n')
+            .unwrap_or(input.len());
+        let mut heights = Vec::with_capacity(input.len());
+        let mut rows = 0;
+        let mut input = input;
+        while !input.is_empty() {
+            heights.extend(input[..cols].iter().map(|b| b - b'0'));
+            rows += 1;
+            input = parse::seek_next_line(&input[cols..]);
+        }
+        Self {
+            rows,
+            cols,
+            heights,
         }
-        input = parse::seek_next_line(input);
     }
 
-    let mut visible: AHashSet<(u32, u32)> = AHashSet::with_capacity(MAX_COLS * MAX_ROWS);
+    pub fn height(&self, y: usize, x: usize) -> u8 {
+        self.heights[y * self.cols + x]
+    }
 
-    // check from WEST to EAST
-    for y in 0..rows {
-        let mut max: i8 = i8::MIN;
+    /// Compute visibility and scenic scores of every tree.
+    pub fn analyze(&self) -> Analysis {
+        let n = self.rows * self.cols;
+        let mut analysis = Analysis {
+            rows: self.rows,
+            cols: self.cols,
+            visible_from: vec![0; n],
+            scenic_scores: vec![1; n],
+        };
+        let mut stack = Vec::with_capacity(self.rows.max(self.cols));
+        let cols = self.cols;
+        for y in 0..self.rows {
+            let row = y * cols..(y + 1) * cols;
+            self.sweep(row.clone(), WEST, &mut analysis, &mut stack);
+            self.sweep(row.rev(), EAST, &mut analysis, &mut stack);
+        }
         for x in 0..cols {
-            let value = unsafe { *grid.get_unchecked(y as usize).get_unchecked(x as usize) };
-            // everything smaller than max is a candidate
-            if value > max {
-                // point can be seen from the outside
-                visible.insert((y, x));
-                max = value;
-            }
+            let col = (0..self.rows).map(|y| y * cols + x);
+            self.sweep(col.clone(), NORTH, &mut analysis, &mut stack);
+            self.sweep(col.rev(), SOUTH, &mut analysis, &mut stack);
         }
+        analysis
     }
 
-    // check from EAST to WEST
-    for y in 0..rows {
-        let mut max: i8 = i8::MIN;
-        for x in (0..cols).rev() {
-            let value = unsafe { *grid.get_unchecked(y as usize).get_unchecked(x as usize) };
-            // everything smaller than max is a candidate
-            if value > max {
-                // point can be seen from the outside
-                visible.insert((y, x));
-                max = value;
+    /// Walk along a line of trees, looking back towards `direction`.
+    ///
+    /// The stack holds the trees which are not hidden behind a later tree that is at least as
+    /// high, so its heights never increase. This makes each sweep linear.
+    fn sweep(
+        &self,
+        line: impl Iterator<Item = usize>,
+        direction: u8,
+        analysis: &mut Analysis,
+        stack: &mut Vec<(usize, u8)>,
+    ) {
+        stack.clear();
+        for (pos, idx) in line.enumerate() {
+            let height = self.heights[idx];
+            while let Some(&(_, h)) = stack.last() {
+                if h >= height {
+                    break;
+                }
+                stack.pop();
             }
+            let viewing_distance = match stack.last() {
+                // view is blocked by the first tree which is at least as high
+                Some(&(blocker, _)) => pos - blocker,
+                None => {
+                    analysis.visible_from[idx] |= direction;
+                    pos
+                }
+            };
+            analysis.scenic_scores[idx] *= viewing_distance as u64;
+            stack.push((pos, height));
         }
     }
+}
 
-    // check from NORTH to SOUTH
-    for x in 0..cols {
-        let mut max: i8 = i8::MIN;
-        for y in 0..rows {
-            let value = unsafe { *grid.get_unchecked(y as usize).get_unchecked(x as usize) };
-            // everything smaller than max is a candidate
-            if value > max {
-                // point can be seen from the outside
-                visible.insert((y, x));
-                max = value;
-            }
-        }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub rows: usize,
+    pub cols: usize,
+    /// Bitmask of `NORTH`, `EAST`, `SOUTH` and `WEST` for every tree (row-major).
+    pub visible_from: Vec<u8>,
+    /// Scenic score of every tree (row-major).
+    pub scenic_scores: Vec<u64>,
+}
+
+impl Analysis {
+    pub fn visible_count(&self) -> usize {
+        self.visible_from
+            .iter()
+            .filter(|&&flags| flags != 0)
+            .count()
     }
 
-    // check from SOUTH to NORTH
-    for x in 0..cols {
-        let mut max: i8 = i8::MIN;
-        for y in (0..rows).rev() {
-            let value = unsafe { *grid.get_unchecked(y as usize).get_unchecked(x as usize) };
-            // everything smaller than max is a candidate
-            if value > max {
-                // point can be seen from the outside
-                visible.insert((y, x));
-                max = value;
-            }
-        }
+    pub fn max_scenic_score(&self) -> u64 {
+        self.scenic_scores.iter().copied().max().unwrap_or(0)
     }
-    let part1 = visible.len();
 
-    // part 2
-    let mut part2: u32 = 0;
-    for y in 0..rows {
-        for x in 0..cols {
-            let value = unsafe { *grid.get_unchecked(y as usize).get_unchecked(x as usize) };
-            let scenic_up = (0..y)
-                .rev()
-                .find(|&y_above| unsafe {
-                    *grid
-                        .get_unchecked(y_above as usize)
-                        .get_unchecked(x as usize)
-                        >= value
-                })
-                .map(|y_first_bad| y - y_first_bad)
-                .unwrap_or(y);
-            let scenic_down = (y + 1..rows)
-                .find(|&y_down| unsafe {
-                    *grid
-                        .get_unchecked(y_down as usize)
-                        .get_unchecked(x as usize)
-                        >= value
-                })
-                .map(|y_first_bad| y_first_bad - y)
-                .unwrap_or((rows - 1) - y);
-            let scenic_left = (0..x)
-                .rev()
-                .find(|&x_left| unsafe {
-                    *grid
-                        .get_unchecked(y as usize)
-                        .get_unchecked(x_left as usize)
-                        >= value
-                })
-                .map(|x_first_bad| x - x_first_bad)
-                .unwrap_or(x);
-            let scenic_right = (x + 1..cols)
-                .find(|&x_right| unsafe {
-                    *grid
-                        .get_unchecked(y as usize)
-                        .get_unchecked(x_right as usize)
-                        >= value
-                })
-                .map(|x_first_bad| x_first_bad - x)
-                .unwrap_or((cols - 1) - x);
-            let scenic_score = scenic_left * scenic_right * scenic_up * scenic_down;
-            if scenic_score > part2 {
-                part2 = scenic_score;
+    /// One line per tree: `row,col,height,visible_from,scenic_score`, where `visible_from` lists
+    /// the directions as letters, e.g. `NW`.
+    pub fn write_csv<W: Write>(&self, forest: &Forest, mut w: W) -> io::Result<()> {
+        writeln!(w, "row,col,height,visible_from,scenic_score")?;
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                let idx = y * self.cols + x;
+                let flags = self.visible_from[idx];
+                let mut dirs = String::with_capacity(4);
+                for (flag, c) in [(NORTH, 'N'), (EAST, 'E'), (SOUTH, 'S'), (WEST, 'W')] {
+                    if flags & flag != 0 {
+                        dirs.push(c);
+                    }
+                }
+                writeln!(
+                    w,
+                    "{y},{x},{},{dirs},{}",
+                    forest.height(y, x),
+                    self.scenic_scores[idx]
+                )?;
             }
         }
+        Ok(())
     }
+
+    /// Heatmap of the scenic scores as binary PGM image (brighter is better).
+    pub fn write_pgm<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "P5\n{} {}\n255\n", self.cols, self.rows)?;
+        let max = self.max_scenic_score().max(1) as f64;
+        let pixels: Vec<u8> = self
+            .scenic_scores
+            .iter()
+            .map(|&score| (score as f64 / max * 255.0).round() as u8)
+            .collect();
+        w.write_all(&pixels)
+    }
+}
+
+pub fn solve(input: &[u8]) -> (String, String) {
+    let forest = Forest::parse(input);
+    let analysis = forest.analyze();
+    let part1 = analysis.visible_count();
+    let part2 = analysis.max_scenic_score();
     (part1.to_string(), part2.to_string())
 }
 
@@ -141,19 +172,45 @@ mod tests {
 
     const DAY: i32 = 08;
 
-    #[test]
-    fn example() {
-        let input = b"30373
+    const EXAMPLE: &[u8] = b"30373
 25512
 65332
 33549
 35390
 ";
-        let solution = solve(input);
+
+    #[test]
+    fn example() {
+        let solution = solve(EXAMPLE);
         assert_eq!("21", solution.0);
         assert_eq!("8", solution.1);
     }
 
+    #[test]
+    fn analysis() {
+        let forest = Forest::parse(&b"123\n456\n"[..]);
+        assert_eq!((2, 3), (forest.rows, forest.cols));
+
+        let forest = Forest::parse(EXAMPLE);
+        let analysis = forest.analyze();
+        // top-left 5 is visible from the north and the west
+        assert_eq!(NORTH | WEST, analysis.visible_from[6]);
+        // middle 5 in the second row
+        assert_eq!(4, analysis.scenic_scores[7]);
+        assert_eq!(8, analysis.scenic_scores[3 * 5 + 2]);
+
+        let mut csv = Vec::new();
+        analysis.write_csv(&forest, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(26, csv.lines().count());
+        assert_eq!(Some("1,1,5,NW,1"), csv.lines().nth(7));
+
+        let mut pgm = Vec::new();
+        analysis.write_pgm(&mut pgm).unwrap();
+        assert!(pgm.starts_with(b"P5\n5 5\n255\n"));
+        assert_eq!(255, pgm[pgm.len() - 25 + 17]);
+    }
+
     #[test]
     fn part1_and_part2() {
         let answer = solve(&aoc_lib::io::read_input(DAY).unwrap());
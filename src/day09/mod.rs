@@ -1,30 +1,49 @@
 use ahash::AHashSet;
-use arrayvec::ArrayVec;
 use log::{debug, trace};
 
 use aoc_lib::{parse, point::Point2D};
 
-#[derive(Debug, Copy, Clone)]
-enum Direction {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
     R,
     D,
     L,
     U,
+    UR,
+    UL,
+    DR,
+    DL,
 }
 
-fn move_point(p: &mut Point2D, direction: Direction) {
-    match direction {
-        Direction::R => {
-            p.x += 1;
-        }
-        Direction::L => {
-            p.x -= 1;
-        }
-        Direction::D => {
-            p.y -= 1;
-        }
-        Direction::U => {
-            p.y += 1;
+impl Direction {
+    pub fn parse(s: &[u8]) -> Option<Self> {
+        use Direction::*;
+        let direction = match s {
+            b"R" => R,
+            b"D" => D,
+            b"L" => L,
+            b"U" => U,
+            b"UR" | b"RU" => UR,
+            b"UL" | b"LU" => UL,
+            b"DR" | b"RD" => DR,
+            b"DL" | b"LD" => DL,
+            _ => return None,
+        };
+        Some(direction)
+    }
+
+    /// returns (dx, dy)
+    pub fn delta(&self) -> (i64, i64) {
+        use Direction::*;
+        match self {
+            R => (1, 0),
+            D => (0, -1),
+            L => (-1, 0),
+            U => (0, 1),
+            UR => (1, 1),
+            UL => (-1, 1),
+            DR => (1, -1),
+            DL => (-1, -1),
         }
     }
 }
@@ -40,73 +59,116 @@ fn follow_head(head: &Point2D, tail: &Point2D) -> Point2D {
     *tail
 }
 
-const MAX_LEN: usize = 10;
-
 #[derive(Debug, PartialEq, Eq)]
-struct Rope {
+pub struct Rope {
     // head is at pos 0, tail is last pos
-    points: ArrayVec<Point2D, MAX_LEN>,
+    knots: Vec<Point2D>,
+    // points visited by each knot
+    visited: Vec<AHashSet<Point2D>>,
+    // position of each knot after every step, if requested
+    trajectories: Option<Vec<Vec<Point2D>>>,
 }
 
 impl Rope {
     pub fn new(len: usize) -> Self {
-        let mut points: ArrayVec<Point2D, MAX_LEN> = ArrayVec::new();
-        for _i in 0..len {
-            points.push(Point2D::new(0, 0));
+        assert!(len > 0, "a rope needs at least one knot");
+        let start = Point2D::new(0, 0);
+        let visited = (0..len)
+            .map(|_| {
+                let mut set = AHashSet::with_capacity(6000);
+                set.insert(start);
+                set
+            })
+            .collect();
+        Self {
+            knots: vec![start; len],
+            visited,
+            trajectories: None,
         }
-        Self { points }
     }
 
-    pub fn apply(&mut self, direction: Direction) {
-        trace!("points before: {:?}", self.points);
-        move_point(&mut self.points[0], direction);
+    /// Like `new`, but additionally records the full trajectory of every knot.
+    pub fn with_trajectories(len: usize) -> Self {
+        let mut rope = Self::new(len);
+        rope.trajectories = Some(rope.knots.iter().map(|&p| vec![p]).collect());
+        rope
+    }
 
-        let n = self.points.len();
-        for i in 0..n - 1 {
-            let new_tail = follow_head(&self.points[i], &self.points[i + 1]);
-            self.points[i + 1] = new_tail;
-        }
-        trace!("points after: {:?}", self.points);
+    pub fn knots(&self) -> &[Point2D] {
+        &self.knots
     }
-}
 
-pub fn solve(input: &[u8]) -> (String, String) {
-    let mut rope = Rope::new(2);
-    let mut rope2 = Rope::new(10);
+    pub fn tail(&self) -> Point2D {
+        *self.knots.last().unwrap()
+    }
+
+    /// Points visited by knot `idx` (the head is knot 0).
+    pub fn visited(&self, idx: usize) -> &AHashSet<Point2D> {
+        &self.visited[idx]
+    }
 
-    // points visited by tail
-    let mut visited: AHashSet<Point2D> = AHashSet::with_capacity(6000);
-    let mut visited2: AHashSet<Point2D> = AHashSet::with_capacity(6000);
+    /// Positions of knot `idx` after every step, including the start.
+    pub fn trajectory(&self, idx: usize) -> Option<&[Point2D]> {
+        self.trajectories.as_ref().map(|t| t[idx].as_slice())
+    }
 
-    visited.insert(*rope.points.last().unwrap());
-    visited2.insert(*rope2.points.last().unwrap());
+    pub fn apply(&mut self, direction: Direction) {
+        trace!("knots before: {:?}", self.knots);
+        let (dx, dy) = direction.delta();
+        self.knots[0].x += dx;
+        self.knots[0].y += dy;
+        self.visited[0].insert(self.knots[0]);
+
+        for i in 1..self.knots.len() {
+            let new_tail = follow_head(&self.knots[i - 1], &self.knots[i]);
+            if new_tail == self.knots[i] {
+                // the remaining knots won't move either
+                break;
+            }
+            self.knots[i] = new_tail;
+            self.visited[i].insert(new_tail);
+        }
+        if let Some(trajectories) = self.trajectories.as_mut() {
+            for (trajectory, &knot) in trajectories.iter_mut().zip(self.knots.iter()) {
+                trajectory.push(knot);
+            }
+        }
+        trace!("knots after: {:?}", self.knots);
+    }
+}
 
+/// Parse lines like `R 4` or `UL 2`.
+pub fn parse_motions(input: &[u8]) -> Vec<(Direction, u64)> {
+    let mut motions = Vec::with_capacity(2048);
     let mut input = input;
     while !input.is_empty() {
-        let (rest, steps) = parse::positive(&input[2..], false).unwrap();
-        let direction = match input[0] {
-            b'R' => Direction::R,
-            b'D' => Direction::D,
-            b'L' => Direction::L,
-            b'U' => Direction::U,
-            _ => {
-                panic!("unexpected direction");
-            }
-        };
+        let pos_space = input.iter().position(|&b| b == b' ').unwrap();
+        let direction = Direction::parse(&input[..pos_space]).expect("unexpected direction");
+        let (rest, steps) = parse::positive(&input[pos_space + 1..], false).unwrap();
+        motions.push((direction, steps));
+        input = parse::seek_next_line(rest);
+    }
+    motions
+}
+
+pub fn solve(input: &[u8]) -> (String, String) {
+    // the first knot behind the head moves exactly like the tail of a rope with two knots
+    let mut rope = Rope::new(10);
+
+    for (direction, steps) in parse_motions(input) {
         debug!("=== applying {:?} {steps}", direction);
         for _i in 0..steps {
             rope.apply(direction);
-            rope2.apply(direction);
-
-            visited.insert(*rope.points.last().unwrap());
-            visited2.insert(*rope2.points.last().unwrap());
         }
-        debug!("visited2 {} rope2: {:?}", visited2.len(), rope2);
-        input = parse::seek_next_line(rest);
+        debug!(
+            "visited {} knots: {:?}",
+            rope.visited(9).len(),
+            rope.knots()
+        );
     }
 
-    let part1 = visited.len();
-    let part2 = visited2.len();
+    let part1 = rope.visited(1).len();
+    let part2 = rope.visited(9).len();
 
     (part1.to_string(), part2.to_string())
 }
@@ -148,6 +210,31 @@ U 20
         assert_eq!("36", solution.1);
     }
 
+    #[test]
+    fn long_ropes_and_diagonals() {
+        let mut rope = Rope::with_trajectories(100);
+        for _i in 0..150 {
+            rope.apply(Direction::UR);
+        }
+        // the rope is stretched diagonally, so the tail moved 51 times
+        assert_eq!(Point2D::new(51, 51), rope.tail());
+        assert_eq!(52, rope.visited(99).len());
+
+        for (direction, steps) in parse_motions(b"L 3\nDL 1\n") {
+            for _i in 0..steps {
+                rope.apply(direction);
+            }
+        }
+        assert_eq!(Point2D::new(146, 149), rope.knots()[0]);
+        assert_eq!(155, rope.visited(0).len());
+
+        let trajectory = rope.trajectory(99).unwrap();
+        assert_eq!(155, trajectory.len());
+        assert_eq!(Point2D::new(0, 0), trajectory[0]);
+        assert_eq!(rope.tail(), *trajectory.last().unwrap());
+        assert_eq!(None, Rope::new(3).trajectory(0));
+    }
+
     #[test]
     fn part1_and_part2() {
         let answer = solve(&aoc_lib::io::read_input(DAY).unwrap());
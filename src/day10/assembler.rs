@@ -0,0 +1,167 @@
+use std::fmt;
+
+use log::debug;
+
+use super::crt::{BLACK, LIT};
+use super::font::Font;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblerError {
+    /// The bitmap is not a rectangle made of `#` and `.`.
+    Dimensions,
+    UnknownGlyph(char),
+    /// The text with the given number of letters does not fit onto the screen.
+    TooLong(usize),
+    /// No program can draw the pixel of the given (one-based) cycle.
+    Infeasible(usize),
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblerError::Dimensions => write!(f, "bitmap must be a rectangle of '#' and '.'"),
+            AssemblerError::UnknownGlyph(c) => write!(f, "no glyph for '{c}'"),
+            AssemblerError::TooLong(n) => write!(f, "text of length {n} does not fit"),
+            AssemblerError::Infeasible(cycle) => {
+                write!(f, "pixel of cycle {cycle} cannot be drawn")
+            }
+        }
+    }
+}
+
+/// Render `text` in the top left corner of a `cols` x `rows` screen.
+pub fn render_text(
+    text: &str,
+    font: &Font,
+    cols: usize,
+    rows: usize,
+) -> Result<String, AssemblerError> {
+    let n = text.chars().count();
+    if n * font.pitch() > cols + font.pitch() - font.width() || font.height() > rows {
+        return Err(AssemblerError::TooLong(n));
+    }
+    let mut screen = vec![BLACK; cols * rows];
+    for (i, c) in text.chars().enumerate() {
+        if c == ' ' {
+            continue;
+        }
+        let (width, glyph) = font.glyph(c).ok_or(AssemblerError::UnknownGlyph(c))?;
+        // a wide last letter may not fit after all
+        if i * font.pitch() + width > cols {
+            return Err(AssemblerError::TooLong(n));
+        }
+        for (row, line) in glyph.chunks(width).enumerate() {
+            let offset = row * cols + i * font.pitch();
+            screen[offset..offset + line.len()].copy_from_slice(line);
+        }
+    }
+    let mut s = String::with_capacity((cols + 1) * rows);
+    for row in screen.chunks(cols) {
+        s.push_str(&String::from_utf8_lossy(row));
+        s.push('\n');
+    }
+    Ok(s)
+}
+
+pub fn assemble_text(
+    text: &str,
+    font: &Font,
+    cols: usize,
+    rows: usize,
+) -> Result<String, AssemblerError> {
+    assemble_bitmap(&render_text(text, font, cols, rows)?)
+}
+
+// sprite positions left of this don't light up any pixel
+const X_MIN: i64 = -2;
+
+/// Generate a program with as few instructions as possible which draws `screen` on a CRT of
+/// the same size.
+///
+/// Dynamic programming over (number of drawn pixels, value of X). Since the CRT stays dark
+/// once the program has ended, trailing dark pixels don't need any instructions.
+pub fn assemble_bitmap(screen: &str) -> Result<String, AssemblerError> {
+    let (pixels, cols) = parse_bitmap(screen)?;
+    let n = pixels.len();
+    let x_max = cols as i64 + 1;
+    let x_count = (x_max - X_MIN + 1) as usize;
+    let lit = |c: usize, x: i64| {
+        let col = (c % cols) as i64;
+        (x - 1 <= col && col <= x + 1) == pixels[c]
+    };
+    let state = |c: usize, x: i64| c * x_count + (x - X_MIN) as usize;
+
+    const UNREACHED: u32 = u32::MAX;
+    let mut cost = vec![UNREACHED; (n + 1) * x_count];
+    // previous state and the instruction leading to the current one (None for noop)
+    let mut came_from: Vec<(usize, Option<i64>)> = vec![(0, None); (n + 1) * x_count];
+    cost[state(0, 1)] = 0;
+
+    let mut furthest = 0;
+    for c in 0..n {
+        for x in X_MIN..=x_max {
+            let current = state(c, x);
+            if cost[current] == UNREACHED || !lit(c, x) {
+                continue;
+            }
+            furthest = c + 1;
+            let next_cost = cost[current] + 1;
+            let next = state(c + 1, x);
+            if next_cost < cost[next] {
+                cost[next] = next_cost;
+                came_from[next] = (current, None);
+            }
+            if c + 1 < n && lit(c + 1, x) {
+                for new_x in X_MIN..=x_max {
+                    let next = state(c + 2, new_x);
+                    if next_cost < cost[next] {
+                        cost[next] = next_cost;
+                        came_from[next] = (current, Some(new_x - x));
+                    }
+                }
+            }
+        }
+    }
+
+    let dark_from = pixels.iter().rposition(|&p| p).map_or(0, |i| i + 1);
+    let end = (dark_from..=n)
+        .flat_map(|c| (X_MIN..=x_max).map(move |x| state(c, x)))
+        .filter(|&s| cost[s] != UNREACHED)
+        .min_by_key(|&s| cost[s])
+        .ok_or(AssemblerError::Infeasible(furthest + 1))?;
+    debug!("found program with {} instructions", cost[end]);
+
+    let mut lines = Vec::with_capacity(cost[end] as usize);
+    let mut current = end;
+    while current != state(0, 1) {
+        let (prev, instruction) = came_from[current];
+        lines.push(match instruction {
+            Some(v) => format!("addx {v}\n"),
+            None => String::from("noop\n"),
+        });
+        current = prev;
+    }
+    lines.reverse();
+    Ok(lines.concat())
+}
+
+/// Returns the pixels in row-major order and the number of columns.
+fn parse_bitmap(screen: &str) -> Result<(Vec<bool>, usize), AssemblerError> {
+    let mut pixels = Vec::with_capacity(screen.len());
+    let mut cols = None;
+    for line in screen.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || *cols.get_or_insert(line.len()) != line.len() {
+            return Err(AssemblerError::Dimensions);
+        }
+        for b in line.bytes() {
+            match b {
+                LIT => pixels.push(true),
+                BLACK => pixels.push(false),
+                _ => return Err(AssemblerError::Dimensions),
+            }
+        }
+    }
+    let cols = cols.ok_or(AssemblerError::Dimensions)?;
+    Ok((pixels, cols))
+}
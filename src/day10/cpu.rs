@@ -0,0 +1,298 @@
+use std::fmt;
+
+use ahash::AHashMap;
+use log::{debug, trace};
+
+/// Register `X` of the default instruction set.
+pub const X: usize = 0;
+
+/// Changes the registers once an instruction completes; gets the instruction's arguments.
+pub type Effect = fn(&mut [i64], &[i64]);
+
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionDef {
+    pub cycles: usize,
+    pub arity: usize,
+    pub effect: Effect,
+}
+
+/// The instructions a CPU understands, together with its registers.
+#[derive(Debug, Clone)]
+pub struct InstructionSet {
+    defs: AHashMap<String, (usize, InstructionDef)>,
+    names: Vec<String>,
+    /// (name, initial value)
+    registers: Vec<(String, i64)>,
+}
+
+impl Default for InstructionSet {
+    /// `noop` and `addx` operating on a single register `x` which starts at 1.
+    fn default() -> Self {
+        let mut isa = Self::new(&[("x", 1)]);
+        isa.define(
+            "noop",
+            InstructionDef {
+                cycles: 1,
+                arity: 0,
+                effect: |_, _| {},
+            },
+        );
+        isa.define(
+            "addx",
+            InstructionDef {
+                cycles: 2,
+                arity: 1,
+                effect: |regs, args| regs[X] += args[0],
+            },
+        );
+        isa
+    }
+}
+
+impl InstructionSet {
+    pub fn new(registers: &[(&str, i64)]) -> Self {
+        Self {
+            defs: AHashMap::with_capacity(8),
+            names: Vec::with_capacity(8),
+            registers: registers
+                .iter()
+                .map(|&(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+
+    /// Add or replace an instruction.
+    pub fn define(&mut self, name: &str, def: InstructionDef) {
+        assert!(def.cycles > 0, "instructions take at least one cycle");
+        match self.defs.get_mut(name) {
+            Some(existing) => existing.1 = def,
+            None => {
+                self.defs.insert(name.to_string(), (self.names.len(), def));
+                self.names.push(name.to_string());
+            }
+        }
+    }
+
+    pub fn register(&self, name: &str) -> Option<usize> {
+        self.registers.iter().position(|(n, _)| n == name)
+    }
+
+    pub fn register_count(&self) -> usize {
+        self.registers.len()
+    }
+
+    pub fn instruction(&self, name: &str) -> Option<&InstructionDef> {
+        self.defs.get(name).map(|x| &x.1)
+    }
+
+    /// Translate source code into a program; the error names the offending line (one-based).
+    pub fn assemble(&self, source: &[u8]) -> Result<Vec<Instruction>, AssembleError> {
+        let text = String::from_utf8_lossy(source);
+        let mut program = Vec::with_capacity(256);
+        for (i, line) in text.lines().enumerate() {
+            let mut tokens = line.split_ascii_whitespace();
+            let Some(name) = tokens.next() else {
+                continue;
+            };
+            let err = |reason: &str| AssembleError {
+                line: i + 1,
+                reason: reason.to_string(),
+            };
+            let (opcode, def) = self
+                .defs
+                .get(name)
+                .ok_or_else(|| err(&format!("unknown instruction '{name}'")))?;
+            let args = tokens
+                .map(|t| t.parse::<i64>())
+                .collect::<Result<Vec<i64>, _>>()
+                .map_err(|_| err("invalid argument"))?;
+            if args.len() != def.arity {
+                return Err(err(&format!(
+                    "'{name}' expects {} argument(s) but got {}",
+                    def.arity,
+                    args.len()
+                )));
+            }
+            program.push(Instruction {
+                opcode: *opcode,
+                args,
+            });
+        }
+        Ok(program)
+    }
+
+    fn def(&self, opcode: usize) -> &InstructionDef {
+        &self.defs[&self.names[opcode]].1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    opcode: usize,
+    pub args: Vec<i64>,
+}
+
+/// The state of the CPU *during* a cycle.
+#[derive(Debug, Clone, Copy)]
+pub struct Tick<'a> {
+    /// one-based
+    pub cycle: usize,
+    /// index of the instruction being executed
+    pub pc: usize,
+    pub registers: &'a [i64],
+}
+
+pub trait Observer {
+    fn observe(&mut self, tick: &Tick);
+}
+
+/// Records the registers during every cycle.
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    /// (cycle, pc, registers)
+    pub entries: Vec<(usize, usize, Vec<i64>)>,
+}
+
+impl Observer for Trace {
+    fn observe(&mut self, tick: &Tick) {
+        self.entries
+            .push((tick.cycle, tick.pc, tick.registers.to_vec()));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stop before the given cycle is executed.
+    Cycle(usize),
+    /// Stop as soon as an instruction sets a register to the given value.
+    Register(usize, i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// The program ran to completion.
+    Halted,
+    /// Execution can be resumed by calling `run` again.
+    Breakpoint(Breakpoint),
+}
+
+#[derive(Debug, Clone)]
+pub struct Cpu {
+    isa: InstructionSet,
+    program: Vec<Instruction>,
+    registers: Vec<i64>,
+    pc: usize,
+    /// cycles spent on the current instruction
+    elapsed: usize,
+    /// the next cycle to execute (one-based)
+    cycle: usize,
+    breakpoints: Vec<Breakpoint>,
+    /// cycle at which we stopped because of a breakpoint
+    paused_at: Option<usize>,
+}
+
+impl Cpu {
+    pub fn new(isa: InstructionSet, program: Vec<Instruction>) -> Self {
+        let registers = isa.registers.iter().map(|x| x.1).collect();
+        Self {
+            isa,
+            program,
+            registers,
+            pc: 0,
+            elapsed: 0,
+            cycle: 1,
+            breakpoints: Vec::new(),
+            paused_at: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn registers(&self) -> &[i64] {
+        &self.registers
+    }
+
+    /// The next cycle to be executed.
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    /// Run until the program ends or a breakpoint is hit.
+    pub fn run(&mut self, observers: &mut [&mut dyn Observer]) -> Stop {
+        while !self.is_halted() {
+            if self.paused_at != Some(self.cycle) {
+                let hit = self
+                    .breakpoints
+                    .iter()
+                    .find(|&&bp| bp == Breakpoint::Cycle(self.cycle));
+                if let Some(&bp) = hit {
+                    debug!("hit breakpoint {:?}", bp);
+                    self.paused_at = Some(self.cycle);
+                    return Stop::Breakpoint(bp);
+                }
+            }
+            self.paused_at = None;
+            if let Some(bp) = self.step(observers) {
+                debug!("hit breakpoint {:?}", bp);
+                return Stop::Breakpoint(bp);
+            }
+        }
+        Stop::Halted
+    }
+
+    /// Execute a single cycle; returns the register breakpoint hit at the end of it, if any.
+    fn step(&mut self, observers: &mut [&mut dyn Observer]) -> Option<Breakpoint> {
+        let tick = Tick {
+            cycle: self.cycle,
+            pc: self.pc,
+            registers: &self.registers,
+        };
+        trace!("{:?}", tick);
+        for observer in observers.iter_mut() {
+            observer.observe(&tick);
+        }
+        self.cycle += 1;
+        self.elapsed += 1;
+
+        let instruction = &self.program[self.pc];
+        let def = self.isa.def(instruction.opcode);
+        if self.elapsed < def.cycles {
+            return None;
+        }
+        if self.breakpoints.is_empty() {
+            (def.effect)(&mut self.registers, &instruction.args);
+            self.pc += 1;
+            self.elapsed = 0;
+            return None;
+        }
+        let before = self.registers.clone();
+        (def.effect)(&mut self.registers, &instruction.args);
+        self.pc += 1;
+        self.elapsed = 0;
+
+        self.breakpoints.iter().copied().find(|&bp| match bp {
+            Breakpoint::Register(reg, value) => {
+                self.registers[reg] == value && before[reg] != value
+            }
+            Breakpoint::Cycle(_) => false,
+        })
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use super::cpu::{Observer, Tick, X};
+use super::{cycle_to_coords, COLS, ROWS};
+
+pub const LIT: u8 = b'#';
+pub const BLACK: u8 = b'.';
+
+/// Draws a pixel during every cycle if the sprite at register `X` covers it.
+#[derive(Debug, Clone)]
+pub struct Crt {
+    cols: usize,
+    rows: usize,
+    /// row-major
+    pixels: Vec<u8>,
+}
+
+impl Crt {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            cols,
+            rows,
+            pixels: vec![BLACK; cols * rows],
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Self::new(COLS, ROWS)
+    }
+}
+
+impl Observer for Crt {
+    fn observe(&mut self, tick: &Tick) {
+        let (row, col) = cycle_to_coords(tick.cycle, self.cols);
+        if row < self.rows && sprite_visible(col, tick.registers[X]) {
+            self.pixels[row * self.cols + col] = LIT;
+        }
+    }
+}
+
+impl fmt::Display for Crt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.pixels.chunks(self.cols) {
+            for &b in row {
+                write!(f, "{}", b as char)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn sprite_visible(col: usize, x: i64) -> bool {
+    let col = col as i64;
+    x - 1 <= col && col <= x + 1
+}
+
+/// Sums up the signal strength during the 20th cycle and every 40 cycles after that.
+#[derive(Debug, Clone, Default)]
+pub struct SignalSampler {
+    pub sum: i64,
+}
+
+impl Observer for SignalSampler {
+    fn observe(&mut self, tick: &Tick) {
+        if tick.cycle % 40 == 20 {
+            self.sum += tick.registers[X] * tick.cycle as i64;
+        }
+    }
+}
@@ -0,0 +1,167 @@
+/// Letters of the 6 pixel high font used by the puzzles; each glyph is 4 pixels wide, except
+/// for 'Y' which extends into the gap to the next letter.
+pub const FONT_6: &[(char, &str)] = &[
+    ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+    ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+    ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+    ('E', "####\n#...\n###.\n#...\n#...\n####"),
+    ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+    ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+    ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+    ('I', ".###\n..#.\n..#.\n..#.\n..#.\n.###"),
+    ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+    ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+    ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+    ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+    ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+    ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+    ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('Y', "#...#\n#...#\n.#.#.\n..#..\n..#..\n..#.."),
+    ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+];
+
+/// Letters of the 10 pixel high font used by the puzzles; each glyph is 6 pixels wide.
+pub const FONT_10: &[(char, &str)] = &[
+    (
+        'A',
+        "..##..\n.#..#.\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#",
+    ),
+    (
+        'B',
+        "#####.\n#....#\n#....#\n#....#\n#####.\n#....#\n#....#\n#....#\n#....#\n#####.",
+    ),
+    (
+        'C',
+        ".####.\n#....#\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#....#\n.####.",
+    ),
+    (
+        'E',
+        "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n######",
+    ),
+    (
+        'F',
+        "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n#.....",
+    ),
+    (
+        'G',
+        ".####.\n#....#\n#.....\n#.....\n#.....\n#..###\n#....#\n#....#\n#...##\n.###.#",
+    ),
+    (
+        'H',
+        "#....#\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#\n#....#",
+    ),
+    (
+        'J',
+        "...###\n....#.\n....#.\n....#.\n....#.\n....#.\n....#.\n#...#.\n#...#.\n.###..",
+    ),
+    (
+        'K',
+        "#....#\n#...#.\n#..#..\n#.#...\n##....\n##....\n#.#...\n#..#..\n#...#.\n#....#",
+    ),
+    (
+        'L',
+        "#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n######",
+    ),
+    (
+        'N',
+        "#....#\n##...#\n##...#\n#.#..#\n#.#..#\n#..#.#\n#..#.#\n#...##\n#...##\n#....#",
+    ),
+    (
+        'P',
+        "#####.\n#....#\n#....#\n#....#\n#####.\n#.....\n#.....\n#.....\n#.....\n#.....",
+    ),
+    (
+        'R',
+        "#####.\n#....#\n#....#\n#....#\n#####.\n#..#..\n#...#.\n#...#.\n#....#\n#....#",
+    ),
+    (
+        'X',
+        "#....#\n#....#\n.#..#.\n.#..#.\n..##..\n..##..\n.#..#.\n.#..#.\n#....#\n#....#",
+    ),
+    (
+        'Z',
+        "######\n.....#\n.....#\n....#.\n...#..\n..#...\n.#....\n#.....\n#.....\n######",
+    ),
+];
+
+/// A set of equally high glyphs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Font {
+    /// width of the narrowest glyph
+    width: usize,
+    height: usize,
+    /// blank columns between two letters; wider glyphs may use them up
+    spacing: usize,
+    /// (letter, width, pixels in row-major order)
+    glyphs: Vec<(char, usize, Vec<u8>)>,
+}
+
+impl Font {
+    /// Build a font from glyph drawings made of `#` and `.`, one line per row.
+    ///
+    /// Panics if the glyphs don't all have the same height, or if one of them doesn't fit
+    /// between the left edges of two adjacent letters.
+    pub fn new(spacing: usize, glyphs: &[(char, &str)]) -> Self {
+        assert!(!glyphs.is_empty(), "a font needs at least one glyph");
+        let height = glyphs[0].1.lines().count();
+        let glyphs: Vec<(char, usize, Vec<u8>)> = glyphs
+            .iter()
+            .map(|&(c, drawing)| {
+                let rows: Vec<&str> = drawing.lines().collect();
+                let width = rows[0].len();
+                assert!(
+                    rows.len() == height && rows.iter().all(|r| r.len() == width),
+                    "glyph '{c}' is not {width}x{height} pixels"
+                );
+                (c, width, rows.concat().into_bytes())
+            })
+            .collect();
+        let width = glyphs.iter().map(|&(_, w, _)| w).min().unwrap();
+        if let Some((c, w, _)) = glyphs.iter().find(|&&(_, w, _)| w > width + spacing) {
+            panic!("glyph '{c}' is {w} pixels wide, but letters are only {width} apart");
+        }
+        Self {
+            width,
+            height,
+            spacing,
+            glyphs,
+        }
+    }
+
+    /// The 4x6 font, e.g. on the CRT of day 10.
+    pub fn small() -> Self {
+        Self::new(1, FONT_6)
+    }
+
+    /// The 6x10 font, e.g. of the stars in 2018's day 10.
+    pub fn large() -> Self {
+        Self::new(2, FONT_10)
+    }
+
+    /// Width of the narrowest glyph.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Distance between the left edges of two adjacent letters.
+    pub fn pitch(&self) -> usize {
+        self.width + self.spacing
+    }
+
+    /// The width and the pixels (in row-major order) of `c`.
+    pub fn glyph(&self, c: char) -> Option<(usize, &[u8])> {
+        self.glyphs
+            .iter()
+            .find(|(x, _, _)| *x == c)
+            .map(|(_, w, g)| (*w, g.as_slice()))
+    }
+
+    pub fn glyphs(&self) -> impl Iterator<Item = (char, usize, &[u8])> {
+        self.glyphs.iter().map(|(c, w, g)| (*c, *w, g.as_slice()))
+    }
+}
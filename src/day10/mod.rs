@@ -1,102 +1,45 @@
-use advent_of_code_ocr::parse_string_to_letters;
-use log::debug;
+pub mod assembler;
+pub mod cpu;
+pub mod crt;
+pub mod font;
+pub mod ocr;
 
-use aoc_lib::parse;
+use log::debug;
 
-#[derive(Debug)]
-struct Addx(i64);
+use cpu::{Cpu, InstructionSet};
+use crt::{Crt, SignalSampler};
+use font::Font;
 
 const COLS: usize = 40;
 const ROWS: usize = 6;
 
 pub fn solve(input: &[u8]) -> (String, String) {
-    let mut input = input;
-
-    let mut x: i64 = 1;
-    let mut cycle: usize = 1;
-
-    let mut part1: i64 = 0;
+    let isa = InstructionSet::default();
+    let program = isa.assemble(input).unwrap();
+    let mut cpu = Cpu::new(isa, program);
 
-    const LIT: u8 = b'#';
-    const BLACK: u8 = b'.';
-
-    let mut crt: [[u8; COLS]; ROWS] = [[BLACK; COLS]; ROWS];
-
-    while !input.is_empty() {
-        debug!("Start cycle {cycle}");
-        {
-            // part 2
-            let (row, col) = cycle_to_coords(cycle);
-            if sprite_visible(col, x) {
-                crt[row][col] = LIT;
-            }
-        }
+    let mut crt = Crt::new(COLS, ROWS);
+    let mut sampler = SignalSampler::default();
+    cpu.run(&mut [&mut crt, &mut sampler]);
 
-        match input[0] {
-            b'a' => {
-                // addx
-                let (rest, n) = parse::integer(&input[5..], false).unwrap();
-                debug!("begin executing addx {n}");
-                input = rest;
-                cycle += 1;
-                {
-                    // part 2
-                    let (row, col) = cycle_to_coords(cycle);
-                    if sprite_visible(col, x) {
-                        crt[row][col] = LIT;
-                    }
-                }
-                if cycle % 40 == 20 {
-                    part1 += x * (cycle as i64);
-                }
-                x += n;
-            }
-            b'n' => {
-                debug!("noop");
-            }
-            _ => panic!("invalid input"),
-        }
-        cycle += 1;
-        {
-            // part 2
-            let (row, col) = cycle_to_coords(cycle);
-            if sprite_visible(col, x) {
-                crt[row][col] = LIT;
-            }
-        }
+    let screen = crt.to_string();
+    debug!("\n{screen}");
+    let part2 = match ocr::recognize(&screen, &Font::small()) {
+        Ok(text) => text,
+        Err(e) => format!("unreadable screen: {e}"),
+    };
 
-        if cycle % 40 == 20 {
-            part1 += x * (cycle as i64);
-        }
-
-        input = parse::seek_next_line(input);
-    }
-
-    let mut s = String::with_capacity(COLS * ROWS + ROWS);
-    for row in crt {
-        for b in row {
-            s.push(b as char);
-        }
-        s.push('\n');
-    }
-    let part2 = parse_string_to_letters(&s);
-
-    (part1.to_string(), part2)
+    (sampler.sum.to_string(), part2)
 }
 
 /// returns (row, col)
-fn cycle_to_coords(cycle: usize) -> (usize, usize) {
+fn cycle_to_coords(cycle: usize, cols: usize) -> (usize, usize) {
     let cycle = cycle - 1;
-    let row = cycle / COLS;
-    let col = cycle % COLS;
+    let row = cycle / cols;
+    let col = cycle % cols;
     (row, col)
 }
 
-fn sprite_visible(col: usize, x: i64) -> bool {
-    let col = col as i64;
-    x - 1 <= col && col <= x + 1
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,15 +197,204 @@ noop
 ";
         let solution = solve(input);
         assert_eq!("13140", solution.0);
+        // the screen shows no letters
+        assert!(solution.1.starts_with("unreadable screen: "));
+    }
+
+    #[test]
+    fn emulator() {
+        use cpu::{Breakpoint, InstructionDef, Stop, Trace, X};
+
+        let mut isa = InstructionSet::new(&[("x", 1), ("y", 0)]);
+        isa.define(
+            "noop",
+            InstructionDef {
+                cycles: 1,
+                arity: 0,
+                effect: |_, _| {},
+            },
+        );
+        isa.define(
+            "addx",
+            InstructionDef {
+                cycles: 2,
+                arity: 1,
+                effect: |regs, args| regs[0] += args[0],
+            },
+        );
+        // y = y * x + arg, takes three cycles
+        isa.define(
+            "fma",
+            InstructionDef {
+                cycles: 3,
+                arity: 1,
+                effect: |regs, args| regs[1] = regs[1] * regs[0] + args[0],
+            },
+        );
+        let y = isa.register("y").unwrap();
+        assert!(isa.assemble(b"noop\nmulx 3\n").is_err());
+        assert_eq!(2, isa.assemble(b"noop\naddx\n").unwrap_err().line);
+
+        let program = isa
+            .assemble(b"noop\naddx 3\nfma 2\naddx -1\nfma 1\n")
+            .unwrap();
+        let mut cpu = Cpu::new(isa, program);
+        cpu.add_breakpoint(Breakpoint::Cycle(4));
+        cpu.add_breakpoint(Breakpoint::Register(y, 2));
+
+        let mut trace = Trace::default();
+        assert_eq!(
+            Stop::Breakpoint(Breakpoint::Cycle(4)),
+            cpu.run(&mut [&mut trace])
+        );
+        assert_eq!(4, cpu.cycle());
+        assert_eq!(&[4, 0], cpu.registers());
+        assert_eq!(
+            Stop::Breakpoint(Breakpoint::Register(y, 2)),
+            cpu.run(&mut [&mut trace])
+        );
+        assert_eq!(7, cpu.cycle());
+        assert_eq!(Stop::Halted, cpu.run(&mut [&mut trace]));
+        assert_eq!(&[3, 7], cpu.registers());
+
+        // x during each of the 11 cycles
+        let xs: Vec<i64> = trace.entries.iter().map(|e| e.2[X]).collect();
+        assert_eq!(vec![1, 1, 1, 4, 4, 4, 4, 4, 3, 3, 3], xs);
+        assert_eq!((6, 2, vec![4, 0]), trace.entries[5]);
+    }
+
+    #[test]
+    fn assembler() {
+        use assembler::{assemble_bitmap, assemble_text, render_text, AssemblerError};
+
+        let run = |source: &str, cols: usize, rows: usize| {
+            let isa = InstructionSet::default();
+            let program = isa.assemble(source.as_bytes()).unwrap();
+            let mut crt = Crt::new(cols, rows);
+            Cpu::new(isa, program).run(&mut [&mut crt]);
+            crt.to_string()
+        };
+        let font = Font::small();
+
+        let screen = render_text("EJCFPGLH", &font, COLS, ROWS).unwrap();
+        let source = assemble_text("EJCFPGLH", &font, COLS, ROWS).unwrap();
+        assert_eq!(run(&source, COLS, ROWS), screen);
+
+        let screen = render_text("EL", &font, 12, 8).unwrap();
+        let source = assemble_text("EL", &font, 12, 8).unwrap();
+        assert_eq!(run(&source, 12, 8), screen);
+
+        // 'Y' is one pixel wider than the other letters
+        let screen = render_text("EYES", &font, 19, 6).unwrap();
+        assert!(screen.starts_with("####.#...#####..###\n"));
+        let source = assemble_text("EYES", &font, 19, 6).unwrap();
+        assert_eq!(run(&source, 19, 6), screen);
+        assert_eq!(
+            Err(AssemblerError::TooLong(2)),
+            render_text("EY", &font, 9, 6)
+        );
+
+        // the sprite starts at x = 1, so the first three pixels are lit anyway
+        let mut bitmap = String::from("###");
+        bitmap.push_str(&".".repeat(COLS - 3));
+        bitmap.push('\n');
+        for _ in 1..ROWS {
+            bitmap.push_str(&".".repeat(COLS));
+            bitmap.push('\n');
+        }
+        let source = assemble_bitmap(&bitmap).unwrap();
+        assert_eq!(2, source.lines().count());
+        assert_eq!(run(&source, COLS, ROWS), bitmap);
+
+        let dark_corner = bitmap.replacen('#', ".", 1);
+        assert_eq!(
+            Err(AssemblerError::Infeasible(1)),
+            assemble_bitmap(&dark_corner)
+        );
+        assert_eq!(
+            Err(AssemblerError::UnknownGlyph('Q')),
+            render_text("Q", &font, COLS, ROWS)
+        );
+        assert_eq!(
+            Err(AssemblerError::TooLong(9)),
+            render_text("ABCDEFGHI", &font, COLS, ROWS)
+        );
+        assert_eq!(Err(AssemblerError::Dimensions), assemble_bitmap("##\n#"));
+    }
+
+    #[test]
+    fn ocr() {
+        use assembler::{assemble_text, render_text};
+        use ocr::{recognize, OcrError};
+
+        let small = Font::small();
+        let source = assemble_text("EJCFPGLH", &small, COLS, ROWS).unwrap();
+        let isa = InstructionSet::default();
+        let program = isa.assemble(source.as_bytes()).unwrap();
+        let mut crt = Crt::default();
+        Cpu::new(isa, program).run(&mut [&mut crt]);
+        assert_eq!(
+            Ok("EJCFPGLH".to_string()),
+            recognize(&crt.to_string(), &small)
+        );
+
+        // 'I' starts with a blank column
+        let screen = render_text("IJ", &small, 10, 6).unwrap();
+        assert_eq!(Ok("IJ".to_string()), recognize(&screen, &small));
+
+        let large = Font::large();
+        let screen = render_text("ZERK", &large, 40, 12).unwrap();
+        assert_eq!(Ok("ZERK".to_string()), recognize(&screen, &large));
+        assert_eq!(
+            Err(OcrError::Height {
+                expected: 6,
+                found: 10
+            }),
+            recognize(&screen, &small)
+        );
+
+        let custom = Font::new(1, &[('T', "###\n.#.\n.#.")]);
+        let screen = ".....\n###..\n.#.##\n.#.#.\n";
+        match recognize(screen, &custom) {
+            Err(OcrError::UnknownGlyphs(glyphs)) => {
+                assert_eq!(1, glyphs.len());
+                assert_eq!(3, glyphs[0].col);
+                assert_eq!("...\n##.\n#..", glyphs[0].drawing);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(
+            Ok("TT".to_string()),
+            recognize("###.###\n.#...#.\n.#...#.", &custom)
+        );
+        assert_eq!(Ok(String::new()), recognize("...\n...", &custom));
+        assert_eq!(Err(OcrError::Malformed), recognize("##\n#", &custom));
+    }
+
+    #[test]
+    fn ocr_reads_every_glyph() {
+        use assembler::render_text;
+        use ocr::recognize;
+
+        for font in [Font::small(), Font::large()] {
+            let letters: String = font.glyphs().map(|(c, _, _)| c).collect();
+            for c in letters.chars() {
+                let screen = render_text(&c.to_string(), &font, font.pitch(), font.height());
+                assert_eq!(Ok(c.to_string()), recognize(&screen.unwrap(), &font));
+            }
+            let cols = letters.len() * font.pitch();
+            let screen = render_text(&letters, &font, cols, font.height()).unwrap();
+            assert_eq!(Ok(letters), recognize(&screen, &font));
+        }
     }
 
     #[test]
     fn test_cycle_to_coords() {
-        assert_eq!((0, 0), cycle_to_coords(1));
-        assert_eq!((0, 39), cycle_to_coords(40));
-        assert_eq!((1, 0), cycle_to_coords(41));
-        assert_eq!((1, 39), cycle_to_coords(80));
-        assert_eq!((5, 0), cycle_to_coords(201));
+        assert_eq!((0, 0), cycle_to_coords(1, COLS));
+        assert_eq!((0, 39), cycle_to_coords(40, COLS));
+        assert_eq!((1, 0), cycle_to_coords(41, COLS));
+        assert_eq!((1, 39), cycle_to_coords(80, COLS));
+        assert_eq!((5, 0), cycle_to_coords(201, COLS));
     }
 
     #[test]
@@ -0,0 +1,136 @@
+use std::fmt;
+
+use super::crt::{BLACK, LIT};
+use super::font::Font;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGlyph {
+    /// column of the leftmost lit pixel
+    pub col: usize,
+    pub drawing: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OcrError {
+    /// The screen is not a rectangle made of `#` and `.`.
+    Malformed,
+    /// The lit pixels span a different number of rows than the glyphs of the font.
+    Height {
+        expected: usize,
+        found: usize,
+    },
+    UnknownGlyphs(Vec<UnknownGlyph>),
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcrError::Malformed => write!(f, "screen must be a rectangle of '#' and '.'"),
+            OcrError::Height { expected, found } => {
+                write!(f, "text is {found} pixels high, but the font is {expected}")
+            }
+            OcrError::UnknownGlyphs(glyphs) => {
+                write!(f, "{} unknown glyph(s)", glyphs.len())?;
+                for glyph in glyphs {
+                    write!(f, "\nat column {}:\n{}", glyph.col, glyph.drawing)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Read the letters drawn on `screen`, which may be anywhere on it.
+///
+/// Fails instead of guessing if any of the drawn glyphs is not part of `font`.
+pub fn recognize(screen: &str, font: &Font) -> Result<String, OcrError> {
+    let rows: Vec<&[u8]> = screen.lines().map(|l| l.trim_end().as_bytes()).collect();
+    let cols = rows.first().map_or(0, |r| r.len());
+    if rows
+        .iter()
+        .any(|r| r.len() != cols || r.iter().any(|&b| b != LIT && b != BLACK))
+    {
+        return Err(OcrError::Malformed);
+    }
+
+    let top = rows.iter().position(|r| r.contains(&LIT));
+    let bottom = rows.iter().rposition(|r| r.contains(&LIT));
+    let (Some(top), Some(bottom)) = (top, bottom) else {
+        return Ok(String::new());
+    };
+    if bottom - top + 1 != font.height() {
+        return Err(OcrError::Height {
+            expected: font.height(),
+            found: bottom - top + 1,
+        });
+    }
+    let rows = &rows[top..=bottom];
+    let width = font.width();
+
+    let blank = |x: usize| rows.iter().all(|r| r[x] != LIT);
+    // the pixels of a `width` wide glyph whose left edge is at `x`; anything outside of the
+    // screen is dark
+    let window = |x: isize, width: usize| -> Vec<u8> {
+        rows.iter()
+            .flat_map(|r| {
+                (x..x + width as isize).map(|col| {
+                    if col >= 0 && (col as usize) < cols {
+                        r[col as usize]
+                    } else {
+                        BLACK
+                    }
+                })
+            })
+            .collect()
+    };
+    // glyphs like 'I' start with blank columns, so their left edge lies before the first lit pixel
+    let mut glyphs: Vec<(char, usize, &[u8], usize)> = font
+        .glyphs()
+        .map(|(c, w, g)| {
+            let lead = (0..w)
+                .position(|dx| g.chunks(w).any(|row| row[dx] == LIT))
+                .unwrap_or(w);
+            (c, w, g, lead)
+        })
+        .collect();
+    // a narrow glyph may look like the left part of a wide one
+    glyphs.sort_by_key(|&(_, w, _, _)| std::cmp::Reverse(w));
+
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+    let mut x = 0;
+    loop {
+        while x < cols && blank(x) {
+            x += 1;
+        }
+        if x >= cols {
+            break;
+        }
+        let hit = glyphs.iter().find_map(|&(c, w, g, lead)| {
+            let start = x as isize - lead as isize;
+            (window(start, w) == g).then_some((c, start + w as isize))
+        });
+        match hit {
+            Some((c, end)) => {
+                text.push(c);
+                x = end as usize;
+            }
+            None => {
+                let pixels = window(x as isize, width);
+                let drawing = pixels
+                    .chunks(width)
+                    .map(|row| String::from_utf8_lossy(row))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                unknown.push(UnknownGlyph { col: x, drawing });
+                x += width;
+            }
+        }
+    }
+
+    if unknown.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::UnknownGlyphs(unknown))
+    }
+}
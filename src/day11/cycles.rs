@@ -0,0 +1,110 @@
+use ahash::AHashMap;
+use log::debug;
+
+use super::{lcm_of_divisors, Monkey, N};
+
+/// The trajectory of an item: after `offset` rounds it repeats every `period` rounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemCycle {
+    pub offset: u64,
+    pub period: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastForward {
+    /// How many items each monkey has inspected.
+    pub inspections: Vec<u64>,
+    /// Per item; `None` if the item's cycle did not close within the requested rounds.
+    pub cycles: Vec<Option<ItemCycle>>,
+}
+
+/// Count the inspections after `rounds` rounds without relief, without simulating all of them.
+///
+/// Items don't influence each other, and the state of an item at the start of a round (its
+/// monkey and its worry level modulo the lcm of the divisors) can take only finitely many
+/// values. So every item's trajectory is eventually periodic and we only have to follow it
+/// until a state repeats.
+pub fn fast_forward(monkeys: &[Monkey], rounds: u64) -> FastForward {
+    let modulus = lcm_of_divisors(monkeys);
+    let mut inspections = vec![0; monkeys.len()];
+    let mut cycles = Vec::new();
+
+    for (start, monkey) in monkeys.iter().enumerate() {
+        for &level in &monkey.items {
+            let cycle = follow(
+                monkeys,
+                modulus,
+                (start, level.rem_euclid(modulus)),
+                rounds,
+                &mut inspections,
+            );
+            debug!("item {}: {:?}", cycles.len(), cycle);
+            cycles.push(cycle);
+        }
+    }
+    FastForward {
+        inspections,
+        cycles,
+    }
+}
+
+/// Add the inspections of a single item to `inspections`.
+fn follow(
+    monkeys: &[Monkey],
+    modulus: N,
+    mut state: (usize, N),
+    rounds: u64,
+    inspections: &mut [u64],
+) -> Option<ItemCycle> {
+    let mut seen: AHashMap<(usize, N), u64> = AHashMap::new();
+    // the monkeys which inspect the item, round after round
+    let mut hops: Vec<usize> = Vec::new();
+    // index into `hops` at which each round starts
+    let mut round_start: Vec<usize> = Vec::new();
+
+    let mut round = 0;
+    let cycle = loop {
+        if round == rounds {
+            break None;
+        }
+        if let Some(&first) = seen.get(&state) {
+            break Some(ItemCycle {
+                offset: first,
+                period: round - first,
+            });
+        }
+        seen.insert(state, round);
+        round_start.push(hops.len());
+
+        // an item is inspected again in the same round if it is thrown to a later monkey
+        let (mut monkey, mut level) = state;
+        loop {
+            hops.push(monkey);
+            let (new_level, dest) = monkeys[monkey].inspect(level, Some(modulus));
+            level = new_level;
+            if dest <= monkey {
+                state = (dest, level);
+                break;
+            }
+            monkey = dest;
+        }
+        round += 1;
+    };
+    round_start.push(hops.len());
+
+    let mut count = |from: u64, to: u64, times: u64| {
+        for &monkey in &hops[round_start[from as usize]..round_start[to as usize]] {
+            inspections[monkey] += times;
+        }
+    };
+    match cycle {
+        None => count(0, round, 1),
+        Some(ItemCycle { offset, period }) => {
+            let remaining = rounds - offset;
+            count(0, offset, 1);
+            count(offset, offset + period, remaining / period);
+            count(offset, offset + remaining % period, 1);
+        }
+    }
+    cycle
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+
+use super::N;
+
+/// The right-hand side of a monkey's `new = ...` operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Old,
+    Const(N),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// byte offset into the expression
+    pub pos: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}: {}", self.pos, self.reason)
+    }
+}
+
+impl Expr {
+    /// Parse an expression made of `old`, non-negative integers, `+`, `-`, `*` and parentheses.
+    pub fn parse(s: &str) -> Result<Expr, ParseError> {
+        let mut parser = Parser {
+            s: s.as_bytes(),
+            pos: 0,
+        };
+        let expr = parser.sum()?;
+        parser.skip_ws();
+        if parser.pos < parser.s.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(expr)
+    }
+
+    pub fn eval(&self, old: N) -> N {
+        match self {
+            Expr::Old => old,
+            Expr::Const(n) => *n,
+            Expr::Add(a, b) => a.eval(old) + b.eval(old),
+            Expr::Sub(a, b) => a.eval(old) - b.eval(old),
+            Expr::Mul(a, b) => a.eval(old) * b.eval(old),
+        }
+    }
+
+    /// Evaluate in Z/mZ; the result is in `0..m`.
+    pub fn eval_mod(&self, old: N, m: N) -> N {
+        let m128 = m as i128;
+        let result = match self {
+            Expr::Old => old as i128,
+            Expr::Const(n) => *n as i128,
+            Expr::Add(a, b) => a.eval_mod(old, m) as i128 + b.eval_mod(old, m) as i128,
+            Expr::Sub(a, b) => a.eval_mod(old, m) as i128 - b.eval_mod(old, m) as i128,
+            Expr::Mul(a, b) => a.eval_mod(old, m) as i128 * b.eval_mod(old, m) as i128,
+        };
+        result.rem_euclid(m128) as N
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Const(n) => write!(f, "{n}"),
+            Expr::Add(a, b) => write!(f, "({a} + {b})"),
+            Expr::Sub(a, b) => write!(f, "({a} - {b})"),
+            Expr::Mul(a, b) => write!(f, "({a} * {b})"),
+        }
+    }
+}
+
+/// Recursive descent; `*` binds stronger than `+` and `-`, all operators are left-associative.
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> ParseError {
+        ParseError {
+            pos: self.pos,
+            reason: reason.to_string(),
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        while self.pos < self.s.len() && self.s[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_ws();
+        self.s.get(self.pos).copied()
+    }
+
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.product()?;
+        while let Some(op @ (b'+' | b'-')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.product()?;
+            lhs = match op {
+                b'+' => Expr::Add(Box::new(lhs), Box::new(rhs)),
+                _ => Expr::Sub(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn product(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.factor()?;
+        while let Some(b'*') = self.peek() {
+            self.pos += 1;
+            let rhs = self.factor()?;
+            lhs = Expr::Mul(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                let expr = self.sum()?;
+                if self.peek() != Some(b')') {
+                    return Err(self.error("expected ')'"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(b'0'..=b'9') => {
+                let start = self.pos;
+                while self.pos < self.s.len() && self.s[self.pos].is_ascii_digit() {
+                    self.pos += 1;
+                }
+                let digits = std::str::from_utf8(&self.s[start..self.pos]).unwrap();
+                digits.parse().map(Expr::Const).map_err(|_| ParseError {
+                    pos: start,
+                    reason: "number too large".to_string(),
+                })
+            }
+            Some(_) if self.s[self.pos..].starts_with(b"old") => {
+                self.pos += 3;
+                Ok(Expr::Old)
+            }
+            Some(_) => Err(self.error("expected 'old', a number or '('")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+}
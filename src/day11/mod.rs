@@ -1,178 +1,229 @@
+pub mod cycles;
+pub mod expr;
+
 use std::collections::VecDeque;
 
-use arrayvec::ArrayVec;
 use log::debug;
+use num_integer::Integer;
 
-use aoc_lib::parse;
+use expr::Expr;
 
 type N = i64;
 
-#[derive(Debug, Copy, Clone)]
-enum Operation {
-    Add(N),
-    Mult(N),
-    Square,
+#[derive(Debug, Clone)]
+pub struct Monkey {
+    pub items: Vec<N>,
+    pub operation: Expr,
+    pub divisor: N,
+    pub dest_true: usize,
+    pub dest_false: usize,
 }
 
-impl Operation {
-    pub fn apply(&self, value: N) -> N {
-        use Operation::*;
-        match self {
-            Add(n) => value + n,
-            Mult(n) => value * n,
-            Square => value * value,
+impl Monkey {
+    /// Inspect an item; returns its new worry level and the monkey it is thrown to.
+    ///
+    /// Without a modulus, the worry level is divided by 3 after the inspection.
+    fn inspect(&self, level: N, modulus: Option<N>) -> (N, usize) {
+        let level = match modulus {
+            Some(m) => self.operation.eval_mod(level, m),
+            None => self.operation.eval(level) / 3,
+        };
+        if level % self.divisor == 0 {
+            (level, self.dest_true)
+        } else {
+            (level, self.dest_false)
         }
     }
 }
 
-#[derive(Debug, Clone)]
-struct Monkey {
-    items: VecDeque<N>,
-    operation: Operation,
-    divisor: N,
-    dest_true: u16,
-    dest_false: u16,
+pub fn solve(input: &[u8]) -> (String, String) {
+    let monkeys = parse_monkeys(input);
+    debug!("monkeys: {:?}", monkeys);
+
+    let mut sim = Simulator::new(&monkeys, true);
+    sim.run(20);
+    let part1 = monkey_business(sim.inspections());
+
+    let mut sim = Simulator::new(&monkeys, false);
+    sim.run(10000);
+    let part2 = monkey_business(sim.inspections());
+
+    (part1.to_string(), part2.to_string())
 }
 
-pub fn solve(input: &[u8]) -> (String, String) {
-    let mut monkeys: ArrayVec<Monkey, 8> = ArrayVec::new();
-    let mut input = input;
-    while !input.is_empty() {
-        if input[0] == b'M' {
-            // parse monkey
-
-            // parse items
-            input = parse::seek_next_line(input);
-            let pos_eol = input
-                .iter()
-                .enumerate()
-                .find(|(_i, &x)| x == b'\n')
-                .unwrap()
-                .0;
-            let items: VecDeque<N> = input[18..pos_eol]
-                .split(|&b| b == b',')
-                .map(|s| {
-                    String::from_utf8_lossy(s)
-                        .trim_start()
-                        .parse::<N>()
-                        .unwrap()
-                })
+pub fn parse_monkeys(input: &[u8]) -> Vec<Monkey> {
+    let text = String::from_utf8_lossy(input);
+    let mut monkeys: Vec<Monkey> = Vec::with_capacity(8);
+    for line in text.lines().map(str::trim) {
+        if line.starts_with("Monkey") {
+            monkeys.push(Monkey {
+                items: Vec::new(),
+                operation: Expr::Old,
+                divisor: 1,
+                dest_true: 0,
+                dest_false: 0,
+            });
+            continue;
+        }
+        let Some(monkey) = monkeys.last_mut() else {
+            continue;
+        };
+        let number = |s: &str| s.trim().parse::<N>().unwrap();
+        if let Some(items) = line.strip_prefix("Starting items:") {
+            monkey.items = items
+                .split(',')
+                .filter(|s| !s.trim().is_empty())
+                .map(number)
                 .collect();
-            debug!("items: {:?}", items);
-
-            // parse operation
-            input = &input[pos_eol + 1..];
-            input = &input[23..];
-            let operation: Operation = match input[0] {
-                b'*' => {
-                    if input[2] == b'o' {
-                        Operation::Square
-                    } else {
-                        let (rest, val) = parse::positive(&input[2..], false).unwrap();
-                        input = rest;
-                        Operation::Mult(val as N)
-                    }
-                }
-                b'+' => {
-                    let (rest, val) = parse::positive(&input[2..], false).unwrap();
-                    input = rest;
-                    Operation::Add(val as N)
-                }
-                _ => panic!("unexpected operation"),
-            };
-            debug!("operation: {:?}", operation);
-
-            // parse test
-            input = parse::seek_next_line(input);
-            let (rest, test) = parse::positive(&input[21..], false).unwrap();
-            input = rest;
-            debug!("test: {test}");
-
-            // dest_true
-            input = parse::seek_next_line(input);
-            let (rest, dest_true) = parse::positive(&input[29..], false).unwrap();
-            input = rest;
-            debug!("dest_true: {dest_true}");
-
-            // dest_false
-            input = parse::seek_next_line(input);
-            let (rest, dest_false) = parse::positive(&input[30..], false).unwrap();
-            input = rest;
-            debug!("dest_false: {dest_false}");
-
-            let monkey = Monkey {
-                items,
-                operation,
-                divisor: test as N,
-                dest_true: dest_true as u16,
-                dest_false: dest_false as u16,
-            };
-            monkeys.push(monkey);
+        } else if let Some(op) = line.strip_prefix("Operation: new =") {
+            monkey.operation =
+                Expr::parse(op).unwrap_or_else(|e| panic!("invalid operation '{op}': {e}"));
+        } else if let Some(divisor) = line.strip_prefix("Test: divisible by") {
+            monkey.divisor = number(divisor);
+            assert!(monkey.divisor > 0, "divisor must be positive");
+        } else if let Some(dest) = line.strip_prefix("If true: throw to monkey") {
+            monkey.dest_true = number(dest) as usize;
+        } else if let Some(dest) = line.strip_prefix("If false: throw to monkey") {
+            monkey.dest_false = number(dest) as usize;
         }
-
-        input = parse::seek_next_line(input);
     }
-    debug!("monkeys: {:?}", monkeys);
-    let monkeys2 = monkeys.clone();
+    for monkey in &monkeys {
+        assert!(
+            monkey.dest_true < monkeys.len() && monkey.dest_false < monkeys.len(),
+            "throw to unknown monkey"
+        );
+    }
+    monkeys
+}
 
-    let part1 = helper(monkeys, 20, true);
-    let part2 = helper(monkeys2, 10000, false);
+/// A monkey throwing an item to another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throw {
+    /// one-based
+    pub round: u64,
+    pub from: usize,
+    pub to: usize,
+    /// worry level after the inspection
+    pub level: N,
+}
 
-    (part1.to_string(), part2.to_string())
+/// Plays keep away; items are numbered in the order in which they appear in the input.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    monkeys: Vec<Monkey>,
+    /// (item, worry level) held by each monkey
+    held: Vec<VecDeque<(usize, N)>>,
+    /// whether worry levels are divided by 3 after each inspection
+    relief: bool,
+    modulus: N,
+    round: u64,
+    inspections: Vec<u64>,
+    traces: Option<Vec<Vec<Throw>>>,
 }
 
-fn helper(mut monkeys: ArrayVec<Monkey, 8>, max_rounds: u32, is_part1: bool) -> u64 {
-    let mut inspection_counters: ArrayVec<u64, 8> = ArrayVec::new();
-    for _i in 0..monkeys.len() {
-        inspection_counters.push(0);
-    }
-
-    // NOTE: each divisor is a prime number, so the lcm is just the product
-    let lcm: N = monkeys.iter().map(|monkey| monkey.divisor).product();
-    let n = monkeys.len();
-    for round_number in 1..=max_rounds {
-        debug!("=== Round {round_number}");
-        for i in 0..n {
-            debug!("Monkey {i}:");
-            let item_count = unsafe { monkeys.get_unchecked(i).items.len() };
-            for _j in 0..item_count {
-                let sender = unsafe { monkeys.get_unchecked_mut(i) };
-                let level = sender.items.pop_front().unwrap();
-                debug!("  Monkey inspects an item with a worry level of {level}.");
-                let counter = unsafe { inspection_counters.get_unchecked_mut(i) };
-                *counter += 1;
-
-                let mut new_level = sender.operation.apply(level);
-                debug!("    Worry level increases to {new_level}.");
-                if is_part1 {
-                    new_level /= 3;
-                    debug!("    Monkey gets bored with item. Worry level is divided by 3 to {new_level}.");
+impl Simulator {
+    pub fn new(monkeys: &[Monkey], relief: bool) -> Self {
+        let mut item = 0;
+        let held = monkeys
+            .iter()
+            .map(|monkey| {
+                monkey
+                    .items
+                    .iter()
+                    .map(|&level| {
+                        item += 1;
+                        (item - 1, level)
+                    })
+                    .collect()
+            })
+            .collect();
+        let modulus = lcm_of_divisors(monkeys);
+        Self {
+            monkeys: monkeys.to_vec(),
+            held,
+            relief,
+            modulus,
+            round: 0,
+            inspections: vec![0; monkeys.len()],
+            traces: None,
+        }
+    }
+
+    /// Record every throw of every item from now on.
+    pub fn with_tracing(mut self) -> Self {
+        let items = self.held.iter().map(|items| items.len()).sum();
+        self.traces = Some(vec![Vec::new(); items]);
+        self
+    }
+
+    /// Number of completed rounds.
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    pub fn modulus(&self) -> N {
+        self.modulus
+    }
+
+    /// How many items each monkey has inspected.
+    pub fn inspections(&self) -> &[u64] {
+        &self.inspections
+    }
+
+    /// The throws of the given item, if tracing is enabled.
+    pub fn trace(&self, item: usize) -> Option<&[Throw]> {
+        self.traces.as_ref()?.get(item).map(|t| t.as_slice())
+    }
+
+    /// The worry levels of the items held by a monkey.
+    pub fn items(&self, monkey: usize) -> impl Iterator<Item = N> + '_ {
+        self.held[monkey].iter().map(|x| x.1)
+    }
+
+    pub fn run(&mut self, rounds: u64) {
+        for _ in 0..rounds {
+            self.play_round();
+        }
+    }
+
+    fn play_round(&mut self) {
+        self.round += 1;
+        debug!("=== Round {}", self.round);
+        for i in 0..self.monkeys.len() {
+            // items thrown to the monkey itself have to wait for the next round
+            for _ in 0..self.held[i].len() {
+                let (item, level) = self.held[i].pop_front().unwrap();
+                self.inspections[i] += 1;
+                let modulus = (!self.relief).then_some(self.modulus);
+                let (level, dest) = self.monkeys[i].inspect(level, modulus);
+                debug!("Monkey {i} throws item {item} with worry level {level} to monkey {dest}.");
+                if let Some(traces) = self.traces.as_mut() {
+                    traces[item].push(Throw {
+                        round: self.round,
+                        from: i,
+                        to: dest,
+                        level,
+                    });
                 }
-                // NOTE: this is the key observation: we can work in Z/nZ where n = lcm due to
-                // Chinese Remainder Theorem
-                new_level %= lcm;
-                let dest = if new_level % sender.divisor == 0 {
-                    debug!(
-                        "    Current worry level is divisible by {}.",
-                        sender.divisor
-                    );
-                    sender.dest_true
-                } else {
-                    debug!(
-                        "    Current worry level is not divisible by {}.",
-                        sender.divisor
-                    );
-                    sender.dest_false
-                };
-                debug!("    Item with worry level {new_level} is thrown to monkey {dest}.");
-                let receiver = unsafe { monkeys.get_unchecked_mut(dest as usize) };
-                receiver.items.push_back(new_level);
+                self.held[dest].push_back((item, level));
             }
         }
     }
-    inspection_counters.sort_unstable();
-    let n = inspection_counters.len();
-    inspection_counters[n - 1] * inspection_counters[n - 2]
+}
+
+/// Only divisibility matters, so without relief we can work in Z/nZ where n is the lcm of all
+/// divisors (Chinese Remainder Theorem).
+fn lcm_of_divisors(monkeys: &[Monkey]) -> N {
+    monkeys
+        .iter()
+        .fold(1, |acc, monkey| acc.lcm(&monkey.divisor))
+}
+
+/// The product of the two highest inspection counts.
+pub fn monkey_business(inspections: &[u64]) -> u64 {
+    let mut counts = inspections.to_vec();
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+    counts.iter().take(2).product()
 }
 
 #[cfg(test)]
@@ -181,9 +232,7 @@ mod tests {
 
     const DAY: i32 = 11;
 
-    #[test]
-    fn example() {
-        let input = b"Monkey 0:
+    const EXAMPLE: &[u8] = b"Monkey 0:
   Starting items: 79, 98
   Operation: new = old * 19
   Test: divisible by 23
@@ -211,11 +260,131 @@ Monkey 3:
     If true: throw to monkey 0
     If false: throw to monkey 1
 ";
-        let solution = solve(input);
+
+    #[test]
+    fn example() {
+        let solution = solve(EXAMPLE);
         assert_eq!("10605", solution.0, "part 1");
         assert_eq!("2713310158", solution.1, "part 2");
     }
 
+    #[test]
+    fn expressions() {
+        let expr = Expr::parse(" (old - 2) * 3 + old*old").unwrap();
+        assert_eq!(3 * 5 + 49, expr.eval(7));
+        assert_eq!(64 % 10, expr.eval_mod(7, 10));
+        assert_eq!(4, Expr::parse("old + old").unwrap().eval(2));
+        assert_eq!(9, Expr::parse("old - 1").unwrap().eval_mod(0, 10));
+        assert_eq!(5, Expr::parse("old - 1 - 1").unwrap().eval(7));
+
+        assert_eq!(5, Expr::parse("old +").unwrap_err().pos);
+        assert_eq!(8, Expr::parse("old * (2").unwrap_err().pos);
+        assert_eq!(0, Expr::parse("x").unwrap_err().pos);
+        assert_eq!(4, Expr::parse("old old").unwrap_err().pos);
+    }
+
+    #[test]
+    fn tracing() {
+        let monkeys = parse_monkeys(EXAMPLE);
+        let mut sim = Simulator::new(&monkeys, true).with_tracing();
+        sim.run(1);
+        assert_eq!(vec![20, 23, 27, 26], sim.items(0).collect::<Vec<_>>());
+        sim.run(1);
+        assert_eq!(2, sim.round());
+        let throw = |round, from, to, level| Throw {
+            round,
+            from,
+            to,
+            level,
+        };
+        assert_eq!(
+            &[
+                throw(1, 0, 3, 500),
+                throw(1, 3, 1, 167),
+                throw(2, 1, 2, 57),
+                throw(2, 2, 3, 1083)
+            ],
+            &sim.trace(0).unwrap()[..4]
+        );
+        assert_eq!(None, Simulator::new(&monkeys, true).trace(0));
+
+        // divisors which are not coprime
+        let mut monkeys = monkeys;
+        monkeys[0].divisor = 4;
+        monkeys[1].divisor = 6;
+        monkeys[2].divisor = 4;
+        monkeys[3].divisor = 10;
+        assert_eq!(60, Simulator::new(&monkeys, false).modulus());
+
+        // any number of monkeys
+        let mut many = Vec::new();
+        for i in 0..12 {
+            many.push(Monkey {
+                items: vec![i as N],
+                operation: Expr::parse("(old + 1) * 2").unwrap(),
+                divisor: 3,
+                dest_true: (i + 1) % 12,
+                dest_false: (i + 11) % 12,
+            });
+        }
+        let mut sim = Simulator::new(&many, false);
+        sim.run(100);
+        assert_eq!(12, sim.inspections().len());
+        // every item is inspected at least once per round
+        assert!(sim.inspections().iter().sum::<u64>() >= 100 * 12);
+    }
+
+    #[test]
+    fn self_throws() {
+        let input = b"Monkey 0:
+  Starting items: 4
+  Operation: new = old * 2
+  Test: divisible by 2
+    If true: throw to monkey 0
+    If false: throw to monkey 1
+
+Monkey 1:
+  Starting items:
+  Operation: new = old + 0
+  Test: divisible by 2
+    If true: throw to monkey 0
+    If false: throw to monkey 0
+";
+        let monkeys = parse_monkeys(input);
+        let mut sim = Simulator::new(&monkeys, true);
+        // an item thrown to the monkey itself waits for the next round
+        sim.run(1);
+        assert_eq!(&[1, 0], sim.inspections());
+        assert_eq!(vec![2], sim.items(0).collect::<Vec<_>>());
+        // a later monkey still gets to inspect it in the same round
+        sim.run(1);
+        assert_eq!(&[2, 1], sim.inspections());
+        assert_eq!(vec![0], sim.items(0).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cycles() {
+        use cycles::fast_forward;
+
+        let monkeys = parse_monkeys(EXAMPLE);
+        for rounds in [0, 1, 20, 1000, 10000] {
+            let mut sim = Simulator::new(&monkeys, false);
+            sim.run(rounds);
+            let ff = fast_forward(&monkeys, rounds);
+            assert_eq!(sim.inspections(), ff.inspections, "{rounds} rounds");
+        }
+        assert_eq!(
+            2713310158,
+            monkey_business(&fast_forward(&monkeys, 10000).inspections)
+        );
+
+        let ff = fast_forward(&monkeys, 1_000_000_000_000);
+        assert_eq!(10, ff.cycles.len());
+        assert!(ff.cycles.iter().all(|c| c.is_some()));
+        // every item is inspected at least once per round
+        assert!(ff.inspections.iter().sum::<u64>() >= 10 * 1_000_000_000_000);
+    }
+
     #[test]
     fn part1_and_part2() {
         let answer = solve(&aoc_lib::io::read_input(DAY).unwrap());
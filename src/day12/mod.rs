@@ -1,121 +1,66 @@
+pub mod query;
+pub mod search;
+
 use aoc_lib::point::Point2D;
 use log::debug;
-use std::{collections::VecDeque, fmt};
+use std::fmt;
 
-const MAX_ROWS: usize = 163;
-const MAX_COLS: usize = 163;
-const EMPTY: char = '.';
+use query::Queries;
+use search::Rules;
 
-const START: char = 'S';
-const START_ELEVATION: char = 'a';
+const START: u8 = b'S';
+const START_ELEVATION: u8 = b'a';
 
-const END: char = 'E';
-const END_ELEVATION: char = 'z';
+const END: u8 = b'E';
+const END_ELEVATION: u8 = b'z';
 
-struct Grid {
-    grid: [[char; MAX_COLS]; MAX_ROWS],
+/// The heightmap; elevations are the letters `a` to `z`.
+#[derive(Debug, Clone)]
+pub struct Grid {
     rows: usize,
     cols: usize,
+    /// row-major
+    heights: Vec<u8>,
     start: Point2D,
     end: Point2D,
 }
 
 impl Grid {
-    pub fn new() -> Self {
-        Self {
-            grid: [[EMPTY; MAX_COLS]; MAX_ROWS],
-            rows: 0,
-            cols: 0,
-            start: Point2D { y: 0, x: 0 },
-            end: Point2D { y: 0, x: 0 },
-        }
+    pub fn rows(&self) -> usize {
+        self.rows
     }
-    pub fn set(&mut self, y: usize, x: usize, c: char) {
-        self.grid[y][x] = c;
-        self.rows = std::cmp::max(self.rows, y + 1);
-        self.cols = std::cmp::max(self.cols, x + 1);
+
+    pub fn cols(&self) -> usize {
+        self.cols
     }
 
-    pub fn edges_reversed(&self, y: usize, x: usize, neighbors: &mut Vec<Point2D>) {
-        neighbors.clear();
-        let directions: [(i64, i64); 4] = [
-            (0, -1), // top
-            (1, 0),  // right
-            (0, 1),  // bottom
-            (-1, 0), // left
-        ];
-        let elevation = unsafe { *self.grid.get_unchecked(y).get_unchecked(x) };
-        let x = x as i64;
-        let y = y as i64;
-        for (dx, dy) in directions {
-            let new_x = x + dx;
-            let new_y = y + dy;
-            if new_x >= 0 && new_y >= 0 && new_x < (self.cols as i64) && new_y < (self.rows as i64)
-            {
-                let neighbor_elevation = self.get(new_y as usize, new_x as usize);
-                // the elevation of the destination square can be *at most one higher* than the elevation of your current square
-                if neighbor_elevation >= elevation
-                    || (elevation as u8) == (neighbor_elevation as u8) + 1
-                {
-                    neighbors.push(Point2D { x: new_x, y: new_y });
-                }
-            }
-        }
+    pub fn start(&self) -> Point2D {
+        self.start
     }
 
-    fn get(&self, y: usize, x: usize) -> char {
-        unsafe { *self.grid.get_unchecked(y).get_unchecked(x) }
+    pub fn end(&self) -> Point2D {
+        self.end
     }
 
-    pub fn shortest_distances(&self, start: Point2D) -> Vec<i64> {
-        // like dijkstra but uses VecDeque instead of PriorityQueue due to edge weight 1
-        const INFINITY: i64 = i64::MAX;
-        //const NO_PREV: i64 = -1;
-        let max_nodes = self.rows * self.cols;
-        let mut queue = VecDeque::with_capacity(max_nodes);
-        let mut dist: Vec<i64> = Vec::with_capacity(max_nodes);
-        //let mut prev: Vec<i64> = Vec::with_capacity(max_nodes);
-
-        {
-            // init distances
-            for _i in 0..max_nodes {
-                dist.push(INFINITY);
-                //prev.push(NO_PREV);
-            }
-            dist[self.two_dim_to_one_dim(start)] = 0;
-        }
+    pub fn elevation(&self, p: Point2D) -> u8 {
+        self.heights[self.index(p)]
+    }
 
-        let edge_weight: i64 = 1;
-        let mut neighbors = Vec::with_capacity(4);
-        queue.push_back((0, start));
-        // pop point p closest to start; its distance is d.
-        while let Some((_d, u)) = queue.pop_front() {
-            self.edges_reversed(u.y as usize, u.x as usize, &mut neighbors);
-            for &v in neighbors.iter() {
-                let u_1d = self.two_dim_to_one_dim(u);
-                let v_1d = self.two_dim_to_one_dim(v);
-                let alt = dist[u_1d] + edge_weight;
-                if alt < dist[v_1d] {
-                    debug!(
-                        "found new shortest path to row {}, col {} with distance {}",
-                        v.y, v.x, alt
-                    );
-                    // found shorter path
-                    dist[v_1d] = alt;
-                    //prev[v_1d] = u_1d as i64;
-                    queue.push_back((alt, v));
-                }
-            }
-        }
-        dist
+    /// All cells, row by row.
+    pub fn points(&self) -> impl Iterator<Item = Point2D> + '_ {
+        (0..self.heights.len()).map(|i| self.point(i))
     }
 
-    // Convert 2d to 1d.
-    pub fn two_dim_to_one_dim(&self, p: Point2D) -> usize {
-        // the reverse is:
-        // (x, y) = (offset % self.cols, offset / self.cols);
+    fn index(&self, p: Point2D) -> usize {
         p.y as usize * self.cols + p.x as usize
     }
+
+    fn point(&self, idx: usize) -> Point2D {
+        Point2D {
+            x: (idx % self.cols) as i64,
+            y: (idx / self.cols) as i64,
+        }
+    }
 }
 
 impl fmt::Display for Grid {
@@ -125,11 +70,8 @@ impl fmt::Display for Grid {
             "Grid (rows: {}, cols: {}, start: {:?}, dest: {:?}):",
             self.rows, self.cols, self.start, self.end
         )?;
-        for row in self.grid.iter().take(self.rows) {
-            for col in row.iter().take(self.cols) {
-                write!(f, "{}", col)?;
-            }
-            writeln!(f)?;
+        for row in self.heights.chunks(self.cols) {
+            writeln!(f, "{}", String::from_utf8_lossy(row))?;
         }
         Ok(())
     }
@@ -138,65 +80,51 @@ impl fmt::Display for Grid {
 pub fn solve(input: &[u8]) -> (String, String) {
     let grid = parse_input(input);
     debug!("{}", grid);
-    let mut alt_starts = Vec::with_capacity(64);
-    for (y, row) in grid.grid.iter().enumerate().take(grid.rows) {
-        for (x, &elevation) in row.iter().enumerate().take(grid.cols) {
-            if elevation == 'a' {
-                let p = Point2D {
-                    x: x as i64,
-                    y: y as i64,
-                };
-                if p != grid.start {
-                    alt_starts.push(p);
-                }
-            }
-        }
-    }
-
-    let dist = grid.shortest_distances(grid.end);
 
-    let part1 = dist[grid.two_dim_to_one_dim(grid.start)];
-    let mut part2 = part1;
-    for start in alt_starts {
-        let d = dist[grid.two_dim_to_one_dim(start)];
-        if d < part2 {
-            part2 = d;
-        }
-    }
+    // searches backwards from the end, which yields the distances of all starting points at once
+    let mut queries = Queries::new(&grid, Rules::default());
+    let (_, part2) = queries.nearest_to(grid.end, START_ELEVATION).unwrap();
+    let part1 = queries.distance(grid.start, grid.end).unwrap();
     (part1.to_string(), part2.to_string())
 }
 
-fn parse_input(input: &[u8]) -> Grid {
-    let mut grid = Grid::new();
-    let mut row: usize = 0;
-    let mut input = input;
-    while !input.is_empty() {
-        for (col, &b) in input.iter().enumerate() {
-            match b as char {
-                '\n' => {
-                    input = &input[col..];
-                    input = &input[std::cmp::min(1, input.len())..];
-                    break;
-                }
+pub fn parse_input(input: &[u8]) -> Grid {
+    let mut heights = Vec::with_capacity(input.len());
+    let (mut rows, mut cols) = (0, 0);
+    let mut start = None;
+    let mut end = None;
+    for line in input.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+        if rows == 0 {
+            cols = line.len();
+        }
+        assert_eq!(cols, line.len(), "row {rows} has a different length");
+        for (col, &b) in line.iter().enumerate() {
+            let p = Point2D {
+                x: col as i64,
+                y: rows as i64,
+            };
+            heights.push(match b {
                 START => {
-                    grid.start = Point2D {
-                        y: row as i64,
-                        x: col as i64,
-                    };
-                    grid.set(row, col, START_ELEVATION);
+                    start = Some(p);
+                    START_ELEVATION
                 }
                 END => {
-                    grid.end = Point2D {
-                        y: row as i64,
-                        x: col as i64,
-                    };
-                    grid.set(row, col, END_ELEVATION);
+                    end = Some(p);
+                    END_ELEVATION
                 }
-                _ => grid.set(row, col, b as char),
-            }
+                b'a'..=b'z' => b,
+                _ => panic!("unexpected elevation {}", b as char),
+            });
         }
-        row += 1;
+        rows += 1;
     }
+    let grid = Grid {
+        rows,
+        cols,
+        heights,
+        start: start.expect("no start"),
+        end: end.expect("no end"),
+    };
     debug_assert!(grid.start != grid.end);
     grid
 }
@@ -221,6 +149,134 @@ abdefghi
         assert_eq!("29", solution.1);
     }
 
+    #[test]
+    fn paths() {
+        use search::Cost;
+
+        let grid = parse_input(EXAMPLE);
+        let rules = Rules::default();
+        let path = grid
+            .shortest_path(grid.start(), grid.end(), &rules)
+            .unwrap();
+        assert_eq!(31, path.len());
+        assert_eq!(31, path.cost);
+        assert_eq!(Some(&grid.start()), path.points.first());
+        assert_eq!(Some(&grid.end()), path.points.last());
+        // every step obeys the rules
+        for w in path.points.windows(2) {
+            assert_eq!(1, w[0].manhattan(w[1]));
+            assert!(grid.elevation(w[1]) <= grid.elevation(w[0]) + 1);
+        }
+        assert_eq!(
+            ">>vv<<<<
+..vvv<<^
+..vv>E^^
+..v>>>^^
+..>>>>>^
+",
+            path.render(&grid)
+        );
+        let back = grid
+            .distances_to(grid.end(), &rules)
+            .path(grid.start())
+            .unwrap();
+        assert_eq!(31, back.cost);
+        assert_eq!(Some(&grid.end()), back.points.last());
+
+        // no way down if descending is not allowed either
+        let strict = Rules {
+            max_down: 0,
+            ..rules
+        };
+        assert_eq!(None, grid.shortest_path(grid.end(), grid.start(), &strict));
+
+        let diagonal = Rules {
+            diagonals: true,
+            ..rules
+        };
+        let path = grid
+            .shortest_path(grid.start(), grid.end(), &diagonal)
+            .unwrap();
+        assert!(path.len() < 31);
+
+        // climbing is expensive: the weighted search agrees with Dijkstra
+        let weighted = Rules {
+            max_up: 2,
+            cost: Cost::Weighted {
+                base: 1,
+                up: 5,
+                down: 0,
+            },
+            ..rules
+        };
+        let path = grid
+            .shortest_path(grid.start(), grid.end(), &weighted)
+            .unwrap();
+        let field = grid.distances_from(grid.start(), &weighted);
+        assert_eq!(field.cost(grid.end()), Some(path.cost));
+        let recomputed: u64 = path
+            .points
+            .windows(2)
+            .map(|w| {
+                weighted
+                    .step(grid.elevation(w[0]), grid.elevation(w[1]))
+                    .unwrap()
+            })
+            .sum();
+        assert_eq!(path.cost, recomputed);
+        // at least 25 units of elevation have to be gained
+        assert!(path.cost >= path.len() as u64 + 5 * 25);
+    }
+
+    #[test]
+    fn queries() {
+        let grid = parse_input(EXAMPLE);
+        let mut queries = Queries::new(&grid, Rules::default());
+        let p = |x, y| Point2D { x, y };
+
+        assert_eq!(Some((p(0, 4), 29)), queries.nearest_to(grid.end(), b'a'));
+        assert_eq!(Some(31), queries.distance(grid.start(), grid.end()));
+        // walking downhill is always possible
+        assert_eq!(Some(1), queries.distance(p(1, 1), p(0, 1)));
+        assert_eq!(Some((p(2, 1), 3)), queries.nearest_from(grid.start(), b'c'));
+        assert_eq!(None, queries.nearest_from(grid.start(), b'Q'));
+
+        // the summit can be reached from everywhere, but it's a dead end for climbers
+        assert!(queries.cut_off_from_summit().is_empty());
+        assert_eq!(40, queries.reachable_from(grid.start()).len());
+        assert_eq!(40, queries.reaching(grid.end()).len());
+
+        let strict = Rules {
+            max_down: 0,
+            ..Rules::default()
+        };
+        let mut strict = Queries::new(&grid, strict);
+        assert_eq!(vec![p(4, 2), grid.end()], strict.reachable_from(grid.end()));
+        let lowlands = vec![p(0, 0), p(1, 0), p(0, 1), p(0, 2), p(0, 3), p(0, 4)];
+        assert_eq!(lowlands, strict.reaching(p(1, 0)));
+        // but climbing the spiral works from everywhere
+        assert!(strict.cut_off_from_summit().is_empty());
+
+        let downhill = Rules {
+            max_up: 0,
+            ..Rules::default()
+        };
+        let cut_off = Queries::new(&grid, downhill).cut_off_from_summit();
+        assert_eq!(38, cut_off.len());
+        assert!(!cut_off.contains(&p(4, 2)));
+
+        // only connected cells of equal elevation form a region
+        let regions = strict.regions();
+        assert_eq!(
+            grid.points().count(),
+            regions.iter().map(|r| r.len()).sum::<usize>()
+        );
+        assert_eq!(lowlands, regions[0]);
+        assert!(regions.contains(&vec![p(4, 2), grid.end()]));
+        // with the default rules every cell can reach every other one
+        assert_eq!(1, queries.regions().len());
+    }
+
     #[test]
     fn part1_and_part2() {
         let answer = solve(&aoc_lib::io::read_input(DAY).unwrap());
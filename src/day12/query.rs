@@ -0,0 +1,166 @@
+use ahash::AHashMap;
+use aoc_lib::point::Point2D;
+use log::debug;
+
+use super::search::{DistanceField, Rules};
+use super::Grid;
+
+/// Answers questions about a heightmap; distance fields are computed once and then cached.
+#[derive(Debug, Clone)]
+pub struct Queries<'a> {
+    grid: &'a Grid,
+    rules: Rules,
+    /// keyed by source cell
+    from: AHashMap<usize, DistanceField>,
+    /// keyed by target cell
+    to: AHashMap<usize, DistanceField>,
+}
+
+impl<'a> Queries<'a> {
+    pub fn new(grid: &'a Grid, rules: Rules) -> Self {
+        Self {
+            grid,
+            rules,
+            from: AHashMap::new(),
+            to: AHashMap::new(),
+        }
+    }
+
+    /// Costs from `source` to every cell.
+    pub fn distances_from(&mut self, source: Point2D) -> &DistanceField {
+        let (grid, rules) = (self.grid, &self.rules);
+        self.from
+            .entry(grid.index(source))
+            .or_insert_with(|| grid.distances_from(source, rules))
+    }
+
+    /// Costs from every cell to `target`.
+    pub fn distances_to(&mut self, target: Point2D) -> &DistanceField {
+        let (grid, rules) = (self.grid, &self.rules);
+        self.to
+            .entry(grid.index(target))
+            .or_insert_with(|| grid.distances_to(target, rules))
+    }
+
+    /// The cost of walking from `a` to `b`; reuses a cached field of either point if possible.
+    pub fn distance(&mut self, a: Point2D, b: Point2D) -> Option<u64> {
+        if let Some(field) = self.to.get(&self.grid.index(b)) {
+            return field.cost(a);
+        }
+        self.distances_from(a).cost(b)
+    }
+
+    /// The closest cell of the given elevation which can be reached from `source`.
+    pub fn nearest_from(&mut self, source: Point2D, elevation: u8) -> Option<(Point2D, u64)> {
+        let grid = self.grid;
+        nearest(grid, self.distances_from(source), elevation)
+    }
+
+    /// The closest cell of the given elevation from which `target` can be reached.
+    pub fn nearest_to(&mut self, target: Point2D, elevation: u8) -> Option<(Point2D, u64)> {
+        let grid = self.grid;
+        nearest(grid, self.distances_to(target), elevation)
+    }
+
+    /// All cells which can be reached from `source`, row by row.
+    pub fn reachable_from(&mut self, source: Point2D) -> Vec<Point2D> {
+        reachable(self.distances_from(source))
+    }
+
+    /// All cells from which `target` can be reached, row by row.
+    pub fn reaching(&mut self, target: Point2D) -> Vec<Point2D> {
+        reachable(self.distances_to(target))
+    }
+
+    /// Cells from which there is no way up to the summit.
+    pub fn cut_off_from_summit(&mut self) -> Vec<Point2D> {
+        self.distances_to(self.grid.end)
+            .costs()
+            .filter(|(_, c)| c.is_none())
+            .map(|(p, _)| p)
+            .collect()
+    }
+
+    /// Partition the map into regions whose cells can all reach each other.
+    ///
+    /// These are the strongly connected components of the step graph (Kosaraju). Regions are
+    /// sorted by their first cell, and so are the cells within a region.
+    pub fn regions(&self) -> Vec<Vec<Point2D>> {
+        let grid = self.grid;
+        let n = grid.heights.len();
+        let mut edges = Vec::with_capacity(8);
+
+        // first pass: order cells by the time their DFS finishes
+        let mut visited = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        let mut stack: Vec<(usize, Vec<usize>)> = Vec::new();
+        for root in 0..n {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            grid.edges(root, &self.rules, false, &mut edges);
+            stack.push((root, edges.iter().map(|e| e.0).collect()));
+            while let Some((u, pending)) = stack.last_mut() {
+                match pending.pop() {
+                    Some(v) if !visited[v] => {
+                        visited[v] = true;
+                        grid.edges(v, &self.rules, false, &mut edges);
+                        stack.push((v, edges.iter().map(|e| e.0).collect()));
+                    }
+                    Some(_) => {}
+                    None => {
+                        order.push(*u);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        // second pass: collect what reaches each root, in reverse finishing order
+        const UNASSIGNED: usize = usize::MAX;
+        let mut region = vec![UNASSIGNED; n];
+        let mut regions: Vec<Vec<Point2D>> = Vec::new();
+        let mut todo = Vec::new();
+        for &root in order.iter().rev() {
+            if region[root] != UNASSIGNED {
+                continue;
+            }
+            let id = regions.len();
+            let mut members = Vec::new();
+            region[root] = id;
+            todo.push(root);
+            while let Some(u) = todo.pop() {
+                members.push(u);
+                grid.edges(u, &self.rules, true, &mut edges);
+                for &(v, _) in &edges {
+                    if region[v] == UNASSIGNED {
+                        region[v] = id;
+                        todo.push(v);
+                    }
+                }
+            }
+            members.sort_unstable();
+            regions.push(members.into_iter().map(|i| grid.point(i)).collect());
+        }
+        debug!("{} regions", regions.len());
+        regions.sort_unstable_by_key(|r| (r[0].y, r[0].x));
+        regions
+    }
+}
+
+fn nearest(grid: &Grid, field: &DistanceField, elevation: u8) -> Option<(Point2D, u64)> {
+    field
+        .costs()
+        .filter(|&(p, _)| grid.elevation(p) == elevation)
+        .filter_map(|(p, c)| Some((p, c?)))
+        .min_by_key(|&(_, c)| c)
+}
+
+fn reachable(field: &DistanceField) -> Vec<Point2D> {
+    field
+        .costs()
+        .filter(|(_, c)| c.is_some())
+        .map(|(p, _)| p)
+        .collect()
+}
@@ -0,0 +1,326 @@
+use std::collections::VecDeque;
+
+use aoc_lib::point::Point2D;
+use binary_heap_plus::{BinaryHeap, MinComparator};
+use log::debug;
+
+use super::Grid;
+
+const UNREACHABLE: u64 = u64::MAX;
+const NO_PREV: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cost {
+    /// Every step costs 1.
+    Uniform,
+    /// A step costs `base` plus `up` (`down`) per unit of elevation gained (lost).
+    Weighted { base: u64, up: u64, down: u64 },
+}
+
+/// Which steps are allowed and what they cost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+    pub max_up: u8,
+    pub max_down: u8,
+    pub diagonals: bool,
+    pub cost: Cost,
+}
+
+impl Default for Rules {
+    /// The rules of the puzzle: climb at most one unit, descend arbitrarily.
+    fn default() -> Self {
+        Self {
+            max_up: 1,
+            max_down: u8::MAX,
+            diagonals: false,
+            cost: Cost::Uniform,
+        }
+    }
+}
+
+impl Rules {
+    /// The cost of stepping from elevation `from` to `to`, if that step is allowed.
+    pub fn step(&self, from: u8, to: u8) -> Option<u64> {
+        let up = to.saturating_sub(from);
+        let down = from.saturating_sub(to);
+        if up > self.max_up || down > self.max_down {
+            return None;
+        }
+        Some(match self.cost {
+            Cost::Uniform => 1,
+            Cost::Weighted {
+                base,
+                up: u,
+                down: d,
+            } => base + u * up as u64 + d * down as u64,
+        })
+    }
+
+    fn directions(&self) -> &'static [(i64, i64)] {
+        const ORTHOGONAL: [(i64, i64); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+        const ALL: [(i64, i64); 8] = [
+            (0, -1),
+            (1, 0),
+            (0, 1),
+            (-1, 0),
+            (1, -1),
+            (1, 1),
+            (-1, 1),
+            (-1, -1),
+        ];
+        if self.diagonals {
+            &ALL
+        } else {
+            &ORTHOGONAL
+        }
+    }
+
+    /// A lower bound on the cost from `a` to `b`; never overestimates, so A* stays exact.
+    fn estimate(&self, grid: &Grid, a: usize, b: usize) -> u64 {
+        let Cost::Weighted { base, up, down } = self.cost else {
+            return 0;
+        };
+        let (pa, pb) = (grid.point(a), grid.point(b));
+        let (dx, dy) = ((pa.x - pb.x).unsigned_abs(), (pa.y - pb.y).unsigned_abs());
+        let steps = if self.diagonals { dx.max(dy) } else { dx + dy };
+        let (ha, hb) = (grid.heights[a], grid.heights[b]);
+        base * steps + up * hb.saturating_sub(ha) as u64 + down * ha.saturating_sub(hb) as u64
+    }
+}
+
+/// A route across the map, from its first to its last point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    pub cost: u64,
+    pub points: Vec<Point2D>,
+}
+
+impl Path {
+    /// Number of steps.
+    pub fn len(&self) -> usize {
+        self.points.len().saturating_sub(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Draw the path onto the map like the puzzle description does.
+    pub fn render(&self, grid: &Grid) -> String {
+        let mut canvas: Vec<Vec<u8>> = vec![vec![b'.'; grid.cols]; grid.rows];
+        for w in self.points.windows(2) {
+            let symbol = match (w[1].x - w[0].x, w[1].y - w[0].y) {
+                (1, 0) => b'>',
+                (-1, 0) => b'<',
+                (0, 1) => b'v',
+                (0, -1) => b'^',
+                (1, -1) | (-1, 1) => b'/',
+                _ => b'\\',
+            };
+            canvas[w[0].y as usize][w[0].x as usize] = symbol;
+        }
+        if let Some(last) = self.points.last() {
+            canvas[last.y as usize][last.x as usize] = b'E';
+        }
+        let mut s = String::with_capacity((grid.cols + 1) * grid.rows);
+        for row in canvas {
+            s.push_str(&String::from_utf8_lossy(&row));
+            s.push('\n');
+        }
+        s
+    }
+}
+
+/// Costs from (or to) a single cell, together with the shortest-path tree.
+#[derive(Debug, Clone)]
+pub struct DistanceField {
+    cols: usize,
+    /// whether the costs are *to* the source rather than *from* it
+    towards: bool,
+    cost: Vec<u64>,
+    /// next cell on the way back to the source
+    prev: Vec<usize>,
+}
+
+impl DistanceField {
+    pub fn cost(&self, p: Point2D) -> Option<u64> {
+        let c = self.cost[self.index(p)];
+        (c != UNREACHABLE).then_some(c)
+    }
+
+    /// The shortest path between `p` and the source, in walking direction.
+    pub fn path(&self, p: Point2D) -> Option<Path> {
+        let cost = self.cost(p)?;
+        let mut points = Vec::new();
+        let mut current = self.index(p);
+        while current != NO_PREV {
+            points.push(Point2D {
+                x: (current % self.cols) as i64,
+                y: (current / self.cols) as i64,
+            });
+            current = self.prev[current];
+        }
+        if !self.towards {
+            points.reverse();
+        }
+        Some(Path { cost, points })
+    }
+
+    /// All cells with their cost, row by row; `None` marks unreachable ones.
+    pub fn costs(&self) -> impl Iterator<Item = (Point2D, Option<u64>)> + '_ {
+        self.cost.iter().enumerate().map(|(i, &c)| {
+            let p = Point2D {
+                x: (i % self.cols) as i64,
+                y: (i / self.cols) as i64,
+            };
+            (p, (c != UNREACHABLE).then_some(c))
+        })
+    }
+
+    fn index(&self, p: Point2D) -> usize {
+        p.y as usize * self.cols + p.x as usize
+    }
+}
+
+impl Grid {
+    /// Cells reachable in a single step from `idx` (or, if `reversed`, those which reach it).
+    pub(super) fn edges(
+        &self,
+        idx: usize,
+        rules: &Rules,
+        reversed: bool,
+        out: &mut Vec<(usize, u64)>,
+    ) {
+        out.clear();
+        let p = self.point(idx);
+        for &(dx, dy) in rules.directions() {
+            let (x, y) = (p.x + dx, p.y + dy);
+            if x < 0 || y < 0 || x >= self.cols as i64 || y >= self.rows as i64 {
+                continue;
+            }
+            let other = y as usize * self.cols + x as usize;
+            let (from, to) = if reversed { (other, idx) } else { (idx, other) };
+            if let Some(cost) = rules.step(self.heights[from], self.heights[to]) {
+                out.push((other, cost));
+            }
+        }
+    }
+
+    /// Cheapest costs from `source` to every cell.
+    pub fn distances_from(&self, source: Point2D, rules: &Rules) -> DistanceField {
+        self.distance_field(source, rules, false)
+    }
+
+    /// Cheapest costs from every cell to `target`.
+    pub fn distances_to(&self, target: Point2D, rules: &Rules) -> DistanceField {
+        self.distance_field(target, rules, true)
+    }
+
+    /// BFS for uniform costs, Dijkstra otherwise.
+    fn distance_field(&self, source: Point2D, rules: &Rules, towards: bool) -> DistanceField {
+        let n = self.rows * self.cols;
+        let mut cost = vec![UNREACHABLE; n];
+        let mut prev = vec![NO_PREV; n];
+        let source = self.index(source);
+        cost[source] = 0;
+        let mut edges = Vec::with_capacity(8);
+
+        if rules.cost == Cost::Uniform {
+            let mut queue = VecDeque::with_capacity(n);
+            queue.push_back(source);
+            while let Some(u) = queue.pop_front() {
+                self.edges(u, rules, towards, &mut edges);
+                for &(v, _) in &edges {
+                    if cost[v] == UNREACHABLE {
+                        cost[v] = cost[u] + 1;
+                        prev[v] = u;
+                        queue.push_back(v);
+                    }
+                }
+            }
+        } else {
+            let mut open: BinaryHeap<(u64, usize), MinComparator> =
+                BinaryHeap::with_capacity_min(n);
+            open.push((0, source));
+            while let Some((d, u)) = open.pop() {
+                if d > cost[u] {
+                    continue;
+                }
+                self.edges(u, rules, towards, &mut edges);
+                for &(v, w) in &edges {
+                    if d + w < cost[v] {
+                        cost[v] = d + w;
+                        prev[v] = u;
+                        open.push((d + w, v));
+                    }
+                }
+            }
+        }
+
+        DistanceField {
+            cols: self.cols,
+            towards,
+            cost,
+            prev,
+        }
+    }
+
+    /// The cheapest path from `from` to `to`; BFS for uniform costs, A* otherwise.
+    pub fn shortest_path(&self, from: Point2D, to: Point2D, rules: &Rules) -> Option<Path> {
+        let n = self.rows * self.cols;
+        let (source, target) = (self.index(from), self.index(to));
+        let mut cost = vec![UNREACHABLE; n];
+        let mut prev = vec![NO_PREV; n];
+        cost[source] = 0;
+        let mut edges = Vec::with_capacity(8);
+
+        if rules.cost == Cost::Uniform {
+            let mut queue = VecDeque::with_capacity(n);
+            queue.push_back(source);
+            while let Some(u) = queue.pop_front() {
+                if u == target {
+                    break;
+                }
+                self.edges(u, rules, false, &mut edges);
+                for &(v, _) in &edges {
+                    if cost[v] == UNREACHABLE {
+                        cost[v] = cost[u] + 1;
+                        prev[v] = u;
+                        queue.push_back(v);
+                    }
+                }
+            }
+        } else {
+            // (f-score, cell)
+            let mut open: BinaryHeap<(u64, usize), MinComparator> =
+                BinaryHeap::with_capacity_min(n);
+            open.push((rules.estimate(self, source, target), source));
+            while let Some((f, u)) = open.pop() {
+                if u == target {
+                    break;
+                }
+                if f > cost[u] + rules.estimate(self, u, target) {
+                    continue;
+                }
+                self.edges(u, rules, false, &mut edges);
+                for &(v, w) in &edges {
+                    let g = cost[u] + w;
+                    if g < cost[v] {
+                        cost[v] = g;
+                        prev[v] = u;
+                        open.push((g + rules.estimate(self, v, target), v));
+                    }
+                }
+            }
+        }
+        debug!("cost from {:?} to {:?}: {}", from, to, cost[target]);
+
+        DistanceField {
+            cols: self.cols,
+            towards: false,
+            cost,
+            prev,
+        }
+        .path(to)
+    }
+}
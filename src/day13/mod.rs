@@ -1,124 +1,92 @@
+pub mod packet;
+
 use log::debug;
-use serde_json::Value;
-use std::cmp::Ordering;
 
-use aoc_lib::parse;
+use packet::{Decision, Packet};
 
 pub fn solve(input: &[u8]) -> (String, String) {
-    let mut part1 = 0;
-    let mut index: u64 = 0;
-    let mut input = input;
-
-    let divider1: Value = serde_json::from_slice(b"[[2]]").unwrap();
-    let divider2: Value = serde_json::from_slice(b"[[6]]").unwrap();
-
-    let mut smaller_count_div1: u64 = 0;
-    let mut smaller_count_div2: u64 = 0;
-
-    while !input.is_empty() {
-        index += 1;
-        debug!("=== {index} ===");
-        let pos_eol = input
-            .iter()
-            .enumerate()
-            .find(|(_i, &x)| x == b'\n')
-            .unwrap()
-            .0;
-        let lhs: Value = serde_json::from_slice(&input[0..pos_eol]).unwrap();
-        input = &input[pos_eol + 1..];
-
-        let pos_eol = input
-            .iter()
-            .enumerate()
-            .find(|(_i, &x)| x == b'\n')
-            .unwrap()
-            .0;
-        let rhs: Value = serde_json::from_slice(&input[0..pos_eol]).unwrap();
-
-        let result = compare_values(&lhs, &rhs);
-        debug!("comparison result: {:?}", result);
-        if result == Ordering::Less {
-            part1 += index;
-        }
-
-        if compare_values(&lhs, &divider1) == Ordering::Less {
-            smaller_count_div1 += 1;
-        }
-        if compare_values(&rhs, &divider1) == Ordering::Less {
-            smaller_count_div1 += 1;
-        }
-        if compare_values(&lhs, &divider2) == Ordering::Less {
-            smaller_count_div2 += 1;
-        }
-        if compare_values(&rhs, &divider2) == Ordering::Less {
-            smaller_count_div2 += 1;
-        }
-
-        input = &input[pos_eol..];
-        input = parse::seek_next_line(input);
-        input = parse::seek_next_line(input);
+    let packets = parse_packets(input);
+
+    let part1: usize = packets
+        .chunks(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] < pair[1])
+        .map(|(i, _)| i + 1)
+        .sum();
+    for (index, decision) in out_of_order(&packets) {
+        debug!("pair {index} is out of order: {decision}");
     }
 
-    let part2 = (smaller_count_div1 + 1) * (smaller_count_div2 + 2);
+    let dividers = [
+        Packet::parse(b"[[2]]").unwrap(),
+        Packet::parse(b"[[6]]").unwrap(),
+    ];
+    let part2 = decode(&packets, &dividers).key();
     (part1.to_string(), part2.to_string())
 }
 
-fn compare_values(lhs: &Value, rhs: &Value) -> Ordering {
-    compare_values_helper(lhs, rhs, 0)
+/// Why each pair (one-based index) which is not in the right order compares the way it does.
+pub fn out_of_order(packets: &[Packet]) -> Vec<(usize, Decision)> {
+    packets
+        .chunks(2)
+        .enumerate()
+        .filter(|(_, pair)| pair.len() == 2)
+        .map(|(i, pair)| (i + 1, pair[0].explain(&pair[1])))
+        .filter(|(_, decision)| decision.ordering() != std::cmp::Ordering::Less)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    /// all packets including the dividers, in the right order
+    pub order: Vec<Packet>,
+    /// one-based positions of the dividers in `order`, in the order the dividers were given
+    pub divider_positions: Vec<usize>,
 }
 
-fn compare_values_helper(lhs: &Value, rhs: &Value, level: usize) -> Ordering {
-    let indent = level * 2;
-    let mut ws = String::with_capacity(indent);
-    for _i in 0..indent {
-        ws.push(' ');
+impl Decoded {
+    /// The product of the divider positions.
+    pub fn key(&self) -> usize {
+        self.divider_positions.iter().product()
     }
-    debug!("{ws}- Compare {:?} vs {:?}", lhs, rhs);
+}
 
-    match (lhs, rhs) {
-        (Value::Number(a), Value::Number(b)) => {
-            // If *both values are integers*, the *lower integer* should come first
-            a.as_i64().unwrap().cmp(&b.as_i64().unwrap())
-        }
-        (Value::Array(xs), Value::Array(ys)) => {
-            // If *both values are lists*, compare the first value of each list, then the second
-            // value, and so on
-            for (x, y) in xs.iter().zip(ys.iter()) {
-                match compare_values_helper(x, y, level + 1) {
-                    Ordering::Less => {
-                        return Ordering::Less;
-                    }
-                    Ordering::Greater => {
-                        return Ordering::Greater;
-                    }
-                    Ordering::Equal => {
-                        // continue with rest of list
-                    }
-                }
-            }
-            // check who ran out of items first
-            xs.len().cmp(&ys.len())
-        }
-        (Value::Number(a), Value::Array(_)) => {
-            let alist = Value::Array(vec![Value::Number(a.clone())]);
-            debug!(
-                "Mixed types; convert left to {:?} and retry comparison",
-                alist
-            );
-            compare_values_helper(&alist, rhs, level + 1)
-        }
-        (Value::Array(_), Value::Number(b)) => {
-            let blist = Value::Array(vec![Value::Number(b.clone())]);
-            debug!(
-                "Mixed types; convert right to {:?} and retry comparison",
-                blist
-            );
-            compare_values_helper(lhs, &blist, level + 1)
-        }
-        _ => {
-            panic!("unsupported JSON value");
+/// Sort the packets together with the dividers; the sort is stable and dividers come before
+/// equal packets.
+pub fn decode(packets: &[Packet], dividers: &[Packet]) -> Decoded {
+    let mut order: Vec<(&Packet, Option<usize>)> = dividers
+        .iter()
+        .enumerate()
+        .map(|(i, d)| (d, Some(i)))
+        .chain(packets.iter().map(|p| (p, None)))
+        .collect();
+    order.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut divider_positions = vec![0; dividers.len()];
+    for (pos, &(_, divider)) in order.iter().enumerate() {
+        if let Some(i) = divider {
+            divider_positions[i] = pos + 1;
         }
     }
+    Decoded {
+        order: order.into_iter().map(|(p, _)| p.clone()).collect(),
+        divider_positions,
+    }
+}
+
+/// Parse all packets; blank lines are skipped.
+pub fn parse_packets(input: &[u8]) -> Vec<Packet> {
+    input
+        .split(|&b| b == b'\n')
+        .enumerate()
+        .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|(i, line)| {
+            let packet = Packet::parse(line)
+                .unwrap_or_else(|e| panic!("invalid packet in line {}: {e}", i + 1));
+            debug!("{packet}");
+            packet
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -127,9 +95,7 @@ mod tests {
 
     const DAY: i32 = 13;
 
-    #[test]
-    fn example() {
-        let input = b"[1,1,3,1,1]
+    const EXAMPLE: &[u8] = b"[1,1,3,1,1]
 [1,1,5,1,1]
 
 [[1],[2,3,4]]
@@ -154,11 +120,97 @@ mod tests {
 [1,[2,[3,[4,[5,6,0]]]],8,9]
 ";
 
-        let solution = solve(input);
+    #[test]
+    fn example() {
+        let solution = solve(EXAMPLE);
         assert_eq!("13", solution.0);
         assert_eq!("140", solution.1);
     }
 
+    #[test]
+    fn packets() {
+        for s in [
+            "[]",
+            "[[[]]]",
+            "[1,[2,[3,[4,[5,6,7]]]],8,9]",
+            "10",
+            "[[4294967295],[]]",
+        ] {
+            assert_eq!(s, s.parse::<Packet>().unwrap().to_string());
+        }
+        assert_eq!(Ok(Packet::Int(7)), Packet::parse(b"  7\n"));
+
+        let err = |s: &str| s.parse::<Packet>().unwrap_err().pos;
+        assert_eq!(4, err("[1,2"));
+        assert_eq!(3, err("[1,]"));
+        assert_eq!(2, err("[1 2]"));
+        assert_eq!(3, err("[1]]"));
+        assert_eq!(1, err("[4294967296]"));
+        assert_eq!(0, err(""));
+
+        let p = |s: &str| s.parse::<Packet>().unwrap();
+        assert!(p("[1,1,3,1,1]") < p("[1,1,5,1,1]"));
+        assert!(p("[[1],[2,3,4]]") < p("[[1],4]"));
+        assert!(p("[9]") > p("[[8,7,6]]"));
+        assert!(p("[[[]]]") > p("[[]]"));
+        assert_eq!(p("2"), p("[[2]]"));
+
+        let mut packets = parse_packets(b"[3]\n\n[[1],2]\n[]\n[[]]\n[1,1]\n");
+        packets.sort_unstable();
+        let sorted: Vec<String> = packets.iter().map(|p| p.to_string()).collect();
+        assert_eq!(vec!["[]", "[[]]", "[1,1]", "[[1],2]", "[3]"], sorted);
+    }
+
+    #[test]
+    fn dividers_and_diagnostics() {
+        use packet::Reason;
+
+        let packets = parse_packets(EXAMPLE);
+        let explained: Vec<String> = out_of_order(&packets)
+            .iter()
+            .map(|(i, d)| format!("{i}: {d}"))
+            .collect();
+        assert_eq!(
+            vec![
+                "3: packet[0][0]: left integer 9 vs. right integer 8",
+                "5: packet: right side ran out of items (4 vs. 3)",
+                "7: packet[0]: right side ran out of items (1 vs. 0)",
+                "8: packet[1][1][1][1][2]: left integer 7 vs. right integer 0",
+            ],
+            explained
+        );
+        let decision = packets[2].explain(&packets[3]);
+        // the integer 4 is compared as [4]
+        assert_eq!(vec![1, 0], decision.path);
+        assert_eq!(Reason::Integers { left: 2, right: 4 }, decision.reason);
+        assert_eq!(Reason::Equal, packets[0].explain(&packets[0]).reason);
+
+        let dividers: Vec<Packet> = ["[[6]]", "[[2]]", "[]"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let decoded = decode(&packets, &dividers);
+        // the empty divider sorts before the packet equal to it
+        assert_eq!(vec![15, 11, 1], decoded.divider_positions);
+        assert_eq!(15 * 11, decoded.key());
+        assert_eq!(19, decoded.order.len());
+        assert!(decoded.order.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!("[]", decoded.order[0].to_string());
+        assert_eq!("[9]", decoded.order[18].to_string());
+    }
+
+    #[test]
+    fn packets_equal_to_dividers() {
+        let parse =
+            |list: &[&str]| -> Vec<Packet> { list.iter().map(|s| s.parse().unwrap()).collect() };
+        let packets = parse(&["[[2]]", "[2]", "[1]"]);
+        let dividers = parse(&["[[2]]", "[[6]]"]);
+        // only the packets which are strictly smaller come before a divider
+        let decoded = decode(&packets, &dividers);
+        assert_eq!(vec![2, 5], decoded.divider_positions);
+        assert_eq!(10, decoded.key());
+    }
+
     #[test]
     fn part1_and_part2() {
         let answer = solve(&aoc_lib::io::read_input(DAY).unwrap());
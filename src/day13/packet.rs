@@ -0,0 +1,234 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Eq)]
+pub enum Packet {
+    Int(u32),
+    List(Vec<Packet>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// byte offset into the input
+    pub pos: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}: {}", self.pos, self.reason)
+    }
+}
+
+impl Packet {
+    /// Parse a packet directly from the input bytes; surrounding whitespace is ignored.
+    pub fn parse(input: &[u8]) -> Result<Packet, ParseError> {
+        let start = input.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        let end = input.len()
+            - input
+                .iter()
+                .rev()
+                .take_while(|b| b.is_ascii_whitespace())
+                .count();
+        let end = end.max(start);
+        let (packet, pos) = parse_value(input, start)?;
+        if pos != end {
+            return Err(ParseError {
+                pos,
+                reason: "unexpected trailing input",
+            });
+        }
+        Ok(packet)
+    }
+
+    /// Compare with `other` and tell where the comparison was decided.
+    pub fn explain(&self, other: &Packet) -> Decision {
+        let mut path = Vec::new();
+        let reason = decide(self, other, &mut path);
+        Decision { path, reason }
+    }
+
+    /// The packet as a list; an integer becomes a list containing only itself.
+    fn as_slice(&self) -> &[Packet] {
+        match self {
+            Packet::Int(_) => std::slice::from_ref(self),
+            Packet::List(xs) => xs,
+        }
+    }
+}
+
+/// Why two packets compare the way they do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Equal,
+    /// two different integers
+    Integers {
+        left: u32,
+        right: u32,
+    },
+    /// one of the lists ran out of items
+    Lengths {
+        left: usize,
+        right: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    /// Indices leading from the outermost list to where the comparison was decided. An integer
+    /// compared with a list counts as a list with a single item.
+    pub path: Vec<usize>,
+    pub reason: Reason,
+}
+
+impl Decision {
+    pub fn ordering(&self) -> Ordering {
+        match self.reason {
+            Reason::Equal => Ordering::Equal,
+            Reason::Integers { left, right } => left.cmp(&right),
+            Reason::Lengths { left, right } => left.cmp(&right),
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "packet")?;
+        for i in &self.path {
+            write!(f, "[{i}]")?;
+        }
+        match self.reason {
+            Reason::Equal => write!(f, ": both sides are equal"),
+            Reason::Integers { left, right } => {
+                write!(f, ": left integer {left} vs. right integer {right}")
+            }
+            Reason::Lengths { left, right } if left < right => {
+                write!(f, ": left side ran out of items ({left} vs. {right})")
+            }
+            Reason::Lengths { left, right } => {
+                write!(f, ": right side ran out of items ({left} vs. {right})")
+            }
+        }
+    }
+}
+
+/// Same as `Ord`, but keeps track of where we are.
+fn decide(lhs: &Packet, rhs: &Packet, path: &mut Vec<usize>) -> Reason {
+    if let (Packet::Int(a), Packet::Int(b)) = (lhs, rhs) {
+        return if a == b {
+            Reason::Equal
+        } else {
+            Reason::Integers {
+                left: *a,
+                right: *b,
+            }
+        };
+    }
+    let (xs, ys) = (lhs.as_slice(), rhs.as_slice());
+    for (i, (x, y)) in xs.iter().zip(ys).enumerate() {
+        path.push(i);
+        let reason = decide(x, y, path);
+        if reason != Reason::Equal {
+            return reason;
+        }
+        path.pop();
+    }
+    if xs.len() == ys.len() {
+        Reason::Equal
+    } else {
+        Reason::Lengths {
+            left: xs.len(),
+            right: ys.len(),
+        }
+    }
+}
+
+fn parse_value(input: &[u8], pos: usize) -> Result<(Packet, usize), ParseError> {
+    let err = |pos, reason| Err(ParseError { pos, reason });
+    match input.get(pos) {
+        Some(b'[') => {
+            let mut items = Vec::new();
+            let mut pos = pos + 1;
+            if input.get(pos) == Some(&b']') {
+                return Ok((Packet::List(items), pos + 1));
+            }
+            loop {
+                let (item, next) = parse_value(input, pos)?;
+                items.push(item);
+                match input.get(next) {
+                    Some(b',') => pos = next + 1,
+                    Some(b']') => return Ok((Packet::List(items), next + 1)),
+                    _ => return err(next, "expected ',' or ']'"),
+                }
+            }
+        }
+        Some(b'0'..=b'9') => {
+            let mut value: u32 = 0;
+            let mut end = pos;
+            while let Some(&b @ b'0'..=b'9') = input.get(end) {
+                value = value
+                    .checked_mul(10)
+                    .and_then(|v| v.checked_add((b - b'0') as u32))
+                    .ok_or(ParseError {
+                        pos,
+                        reason: "integer too large",
+                    })?;
+                end += 1;
+            }
+            Ok((Packet::Int(value), end))
+        }
+        Some(_) => err(pos, "expected '[' or an integer"),
+        None => err(pos, "unexpected end of input"),
+    }
+}
+
+impl FromStr for Packet {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Packet::parse(s.as_bytes())
+    }
+}
+
+impl Ord for Packet {
+    /// Integers compare by value, lists element-wise and then by length; an integer compared
+    /// with a list is treated as a list containing only that integer.
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Packet::Int(a), Packet::Int(b)) => a.cmp(b),
+            _ => self.as_slice().cmp(other.as_slice()),
+        }
+    }
+}
+
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Packet {
+    /// Consistent with `Ord`, so `2` equals `[2]`.
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Packet::Int(n) => write!(f, "{n}"),
+            Packet::List(xs) => {
+                write!(f, "[")?;
+                for (i, x) in xs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{x}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+
+use aoc_lib::point::Point2D;
+use log::debug;
+
+use super::rock::Rock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Air,
+    Rock,
+    Sand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaveConfig {
+    /// Where the sand is poured in; the sources take turns.
+    pub sources: Vec<Point2D>,
+    /// Distance of an infinite floor below the lowest rock; without one, sand falls into the abyss.
+    pub floor: Option<i64>,
+    /// Columns of solid rock on the left and the right.
+    pub walls: Option<(i64, i64)>,
+}
+
+impl Default for CaveConfig {
+    fn default() -> Self {
+        Self {
+            sources: vec![Point2D { x: 500, y: 0 }],
+            floor: None,
+            walls: None,
+        }
+    }
+}
+
+/// What happened to a single grain of sand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grain {
+    Settled(Point2D),
+    /// fell out of the cave
+    Abyss,
+    /// the source is covered with sand
+    Blocked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Drop grain after grain.
+    Simulate,
+    /// Compute the final pile directly; needs a floor, otherwise falls back to simulating.
+    Fill,
+}
+
+/// A dense map of the cave, large enough for all the sand it can hold.
+#[derive(Debug, Clone)]
+pub struct Cave {
+    x_min: i64,
+    cols: usize,
+    rows: usize,
+    /// row of the floor, if any
+    floor: Option<usize>,
+    cells: Vec<Cell>,
+    sources: Vec<Point2D>,
+    /// Per source: the path of the last grain. The next one follows it as long as the path is
+    /// free, so it can start right where the last grain came to rest.
+    paths: Vec<Vec<usize>>,
+    /// whether each source still produces grains which come to rest
+    active: Vec<bool>,
+    sand: usize,
+}
+
+impl Cave {
+    pub fn new(rocks: &[Rock], config: &CaveConfig) -> Self {
+        assert!(!config.sources.is_empty(), "need at least one source");
+        let y_max = rocks
+            .iter()
+            .map(|r| r.y_max)
+            .chain(config.sources.iter().map(|s| s.y))
+            .max()
+            .unwrap();
+        let floor = config.floor.map(|depth| y_max + depth);
+        let rows = floor.unwrap_or(y_max) as usize + 1;
+
+        // sand piles up in a triangle below each source, which is bounded by the floor
+        let spread = |s: &Point2D| floor.map_or(0, |f| f - s.y);
+        let mut x_min = config
+            .sources
+            .iter()
+            .map(|s| s.x - spread(s))
+            .min()
+            .unwrap();
+        let mut x_max = config
+            .sources
+            .iter()
+            .map(|s| s.x + spread(s))
+            .max()
+            .unwrap();
+        for rock in rocks {
+            x_min = x_min.min(rock.x_min);
+            x_max = x_max.max(rock.x_max);
+        }
+        if let Some((left, right)) = config.walls {
+            x_min = x_min.max(left);
+            x_max = x_max.min(right);
+        }
+        // one spare column on each side, from where sand can only fall down
+        let x_min = x_min - 1;
+        let cols = (x_max - x_min + 2) as usize;
+
+        let mut cave = Self {
+            x_min,
+            cols,
+            rows,
+            floor: floor.map(|f| f as usize),
+            cells: vec![Cell::Air; cols * rows],
+            sources: config.sources.clone(),
+            paths: Vec::with_capacity(config.sources.len()),
+            active: vec![true; config.sources.len()],
+            sand: 0,
+        };
+        for p in rocks.iter().flat_map(|r| r.cells()) {
+            cave.set_rock(p);
+        }
+        if let Some(floor) = floor {
+            for x in x_min..x_min + cols as i64 {
+                cave.set_rock(Point2D { x, y: floor });
+            }
+        }
+        if let Some((left, right)) = config.walls {
+            for y in 0..rows as i64 {
+                cave.set_rock(Point2D { x: left, y });
+                cave.set_rock(Point2D { x: right, y });
+            }
+        }
+        cave.paths = config
+            .sources
+            .iter()
+            .map(|&s| vec![cave.index(s).expect("source outside of the walls")])
+            .collect();
+        debug!("cave: {}x{}, x_min: {}", cols, rows, x_min);
+        cave
+    }
+
+    pub fn get(&self, p: Point2D) -> Cell {
+        self.index(p).map_or(Cell::Air, |i| self.cells[i])
+    }
+
+    /// Number of grains which have come to rest.
+    pub fn sand(&self) -> usize {
+        self.sand
+    }
+
+    /// Pour sand until every source is blocked or spills into the abyss; returns the number of
+    /// grains which came to rest.
+    pub fn pour(&mut self) -> usize {
+        let before = self.sand;
+        while self.active.contains(&true) {
+            for i in 0..self.sources.len() {
+                if self.active[i] {
+                    self.active[i] = matches!(self.resume(i), Grain::Settled(_));
+                }
+            }
+        }
+        self.sand - before
+    }
+
+    pub fn settle(&mut self, strategy: Strategy) -> usize {
+        match strategy {
+            Strategy::Simulate => self.pour(),
+            Strategy::Fill => self.fill().unwrap_or_else(|| self.pour()),
+        }
+    }
+
+    /// Fill the cave row by row without simulating single grains; only works with a floor.
+    ///
+    /// Sand ends up exactly in those cells which it can reach: a cell is filled iff it is not
+    /// rock and sand is in one of the three cells above it (or it is a source). The floor
+    /// guarantees that the pile grows until every source is covered. Returns the number of
+    /// grains which came to rest.
+    pub fn fill(&mut self) -> Option<usize> {
+        let floor = self.floor?;
+        let before = self.sand;
+        let starts: Vec<usize> = self
+            .sources
+            .iter()
+            .map(|&s| self.index(s).unwrap())
+            .collect();
+        let mut above = vec![false; self.cols];
+        let mut row = vec![false; self.cols];
+        for y in 0..floor {
+            for x in 0..self.cols {
+                let left = x > 0 && above[x - 1];
+                let right = x + 1 < self.cols && above[x + 1];
+                row[x] = left || above[x] || right;
+            }
+            for &start in starts.iter().filter(|&&s| s / self.cols == y) {
+                row[start % self.cols] = true;
+            }
+            let cells = &mut self.cells[y * self.cols..(y + 1) * self.cols];
+            for (reached, cell) in row.iter_mut().zip(cells) {
+                *reached &= *cell != Cell::Rock;
+                if *reached && *cell == Cell::Air {
+                    *cell = Cell::Sand;
+                    self.sand += 1;
+                }
+            }
+            std::mem::swap(&mut above, &mut row);
+        }
+        self.active.fill(false);
+        Some(self.sand - before)
+    }
+
+    /// Drop a single grain from the given source, starting at the very top.
+    pub fn drop_grain(&mut self, source: usize) -> Grain {
+        let start = self.index(self.sources[source]).unwrap();
+        if self.cells[start] != Cell::Air {
+            return Grain::Blocked;
+        }
+        let mut path = vec![start];
+        let grain = self.fall(&mut path);
+        // the cached path may be outdated now
+        self.paths[source] = path;
+        grain
+    }
+
+    /// Drop a grain from the given source, continuing the path of the previous one.
+    fn resume(&mut self, source: usize) -> Grain {
+        let mut path = std::mem::take(&mut self.paths[source]);
+        // sand from other sources might block the path somewhere in the middle
+        let blocked = if self.sources.len() > 1 {
+            path.iter().position(|&i| self.cells[i] != Cell::Air)
+        } else {
+            path.last()
+                .filter(|&&i| self.cells[i] != Cell::Air)
+                .map(|_| path.len() - 1)
+        };
+        if let Some(pos) = blocked {
+            path.truncate(pos);
+        }
+        let grain = if path.is_empty() {
+            Grain::Blocked
+        } else {
+            self.fall(&mut path)
+        };
+        self.paths[source] = path;
+        grain
+    }
+
+    /// Let the grain at the end of `path` fall until it settles; extends `path` on the way.
+    fn fall(&mut self, path: &mut Vec<usize>) -> Grain {
+        loop {
+            let current = *path.last().unwrap();
+            let (x, y) = (current % self.cols, current / self.cols);
+            if y + 1 == self.rows {
+                return Grain::Abyss;
+            }
+            let below = current + self.cols;
+            let next = [below, below.wrapping_sub(1), below + 1]
+                .into_iter()
+                .zip([true, x > 0, x + 1 < self.cols])
+                .find(|&(i, inside)| !inside || self.cells[i] == Cell::Air);
+            match next {
+                Some((_, false)) => return Grain::Abyss,
+                Some((i, true)) => path.push(i),
+                None => {
+                    self.cells[current] = Cell::Sand;
+                    self.sand += 1;
+                    return Grain::Settled(self.point(current));
+                }
+            }
+        }
+    }
+
+    fn set_rock(&mut self, p: Point2D) {
+        if let Some(i) = self.index(p) {
+            self.cells[i] = Cell::Rock;
+        }
+    }
+
+    fn index(&self, p: Point2D) -> Option<usize> {
+        let x = p.x - self.x_min;
+        if x < 0 || p.y < 0 || x >= self.cols as i64 || p.y >= self.rows as i64 {
+            return None;
+        }
+        Some(p.y as usize * self.cols + x as usize)
+    }
+
+    fn point(&self, idx: usize) -> Point2D {
+        Point2D {
+            x: (idx % self.cols) as i64 + self.x_min,
+            y: (idx / self.cols) as i64,
+        }
+    }
+}
+
+impl fmt::Display for Cave {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, cell) in self.cells.iter().enumerate() {
+            let c = match cell {
+                _ if self.sources.contains(&self.point(i)) && *cell == Cell::Air => '+',
+                Cell::Air => '.',
+                Cell::Rock => '#',
+                Cell::Sand => 'o',
+            };
+            write!(f, "{c}")?;
+            if (i + 1) % self.cols == 0 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
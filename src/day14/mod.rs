@@ -1,13 +1,31 @@
-mod rock;
+pub mod cave;
+pub mod rock;
 
-use ahash::AHashSet;
 use aoc_lib::{parse, point::Point2D};
 use log::{debug, trace};
+
+use cave::{Cave, CaveConfig, Strategy};
 use rock::Rock;
 
 pub fn solve(input: &[u8]) -> (String, String) {
+    let rocks = parse_rocks(input);
+
+    let mut cave = Cave::new(&rocks, &CaveConfig::default());
+    let part1 = cave.pour();
+    debug!("\n{cave}");
+
+    let config = CaveConfig {
+        floor: Some(2),
+        ..Default::default()
+    };
+    let part2 = Cave::new(&rocks, &config).settle(Strategy::Fill);
+
+    (part1.to_string(), part2.to_string())
+}
+
+pub fn parse_rocks(input: &[u8]) -> Vec<Rock> {
     let mut input = input;
-    let mut rocks = RocksCollection::new();
+    let mut rocks = Vec::with_capacity(200);
     while !input.is_empty() {
         let mut points: Vec<Point2D> = Vec::new();
         loop {
@@ -22,154 +40,165 @@ pub fn solve(input: &[u8]) -> (String, String) {
             }
             input = &rest[4..];
         }
-        rocks.add_rock(Rock::new(points));
+        rocks.push(Rock::new(points));
 
         input = parse::seek_next_line(input);
     }
+    rocks
+}
 
-    let part1;
-    {
-        let mut sand_points: AHashSet<Point2D> = AHashSet::with_capacity(1024);
-        loop {
-            let sand = Point2D { x: 500, y: 0 };
-            match simulate(sand, &rocks, &sand_points, false) {
-                Some(dest) => {
-                    debug!(">> simulation finished with dest {:?}", dest);
-                    sand_points.insert(dest);
-                }
-                None => {
-                    break;
-                }
-            }
-        }
-        part1 = sand_points.len();
-    }
-
-    let part2;
-    {
-        let mut sand_points: AHashSet<Point2D> = AHashSet::with_capacity(1024);
-        loop {
-            let sand = Point2D { x: 500, y: 0 };
-            match simulate(sand, &rocks, &sand_points, true) {
-                Some(dest) => {
-                    debug!(">> simulation finished with dest {:?}", dest);
-                    if !sand_points.insert(dest) {
-                        break;
-                    }
-                }
-                None => {
-                    break;
-                }
-            }
-        }
-        part2 = sand_points.len();
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cave::{Cell, Grain};
 
-    (part1.to_string(), part2.to_string())
-}
+    const DAY: i32 = 14;
 
-#[derive(Debug)]
-struct RocksCollection {
-    rocks: Vec<Rock>,
-    y_max: i64,
-}
+    const EXAMPLE: &[u8] = b"498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9
+";
 
-impl RocksCollection {
-    pub fn new() -> Self {
-        RocksCollection {
-            rocks: Vec::with_capacity(200),
-            y_max: i64::MIN,
-        }
+    #[test]
+    fn example() {
+        let solution = solve(EXAMPLE);
+        assert_eq!("24", solution.0, "part 1");
+        assert_eq!("93", solution.1, "part 2");
     }
 
-    pub fn add_rock(&mut self, rock: Rock) {
-        if rock.y_max > self.y_max {
-            self.y_max = rock.y_max;
+    /// Drop every grain from the top, the sources taking turns.
+    fn pour_naively(cave: &mut Cave, sources: usize) -> usize {
+        let mut active = vec![true; sources];
+        while active.contains(&true) {
+            for (i, active) in active.iter_mut().enumerate() {
+                if *active {
+                    *active = matches!(cave.drop_grain(i), Grain::Settled(_));
+                }
+            }
         }
-        self.rocks.push(rock);
+        cave.sand()
     }
 
-    pub fn contains(&self, p: &Point2D) -> bool {
-        for r in &self.rocks {
-            if r.contains(p) {
-                return true;
-            }
+    #[test]
+    fn sand_engine() {
+        let rocks = parse_rocks(EXAMPLE);
+        let mut cave = Cave::new(&rocks, &CaveConfig::default());
+        assert_eq!(24, cave.pour());
+        assert_eq!(0, cave.pour());
+        assert_eq!(Cell::Sand, cave.get(Point2D { x: 500, y: 2 }));
+        assert_eq!(Cell::Rock, cave.get(Point2D { x: 502, y: 9 }));
+        assert_eq!(Cell::Air, cave.get(Point2D { x: 500, y: 0 }));
+        assert_eq!(
+            "\
+.......+....
+............
+.......o....
+......ooo...
+.....#ooo##.
+....o#ooo#..
+...###ooo#..
+.....oooo#..
+..o.ooooo#..
+.#########..
+",
+            cave.to_string()
+        );
+
+        let configs = [
+            CaveConfig {
+                sources: vec![Point2D { x: 500, y: 0 }, Point2D { x: 497, y: 1 }],
+                floor: Some(2),
+                walls: None,
+            },
+            CaveConfig {
+                walls: Some((495, 504)),
+                floor: None,
+                ..Default::default()
+            },
+            CaveConfig {
+                sources: vec![Point2D { x: 496, y: 0 }, Point2D { x: 503, y: 0 }],
+                floor: Some(4),
+                walls: Some((493, 506)),
+            },
+        ];
+        for config in configs {
+            let n = config.sources.len();
+            let resumed = Cave::new(&rocks, &config).pour();
+            let naive = pour_naively(&mut Cave::new(&rocks, &config), n);
+            assert_eq!(naive, resumed, "{config:?}");
         }
-        false
-    }
-}
 
-// Let it snow... Returns destination of sand.
-fn simulate(
-    mut sand: Point2D,
-    rocks: &RocksCollection,
-    sand_points: &AHashSet<Point2D>,
-    check_floor: bool,
-) -> Option<Point2D> {
-    debug!("simulating {:?}, y_max: {:?}", sand, rocks.y_max);
-    let floor = rocks.y_max + 2;
-    loop {
-        let sand_old = sand;
-        let down = Point2D {
-            x: sand.x,
-            y: sand.y + 1,
-        };
-        let down_left = Point2D {
-            x: down.x - 1,
-            y: down.y,
-        };
-        let down_right = Point2D {
-            x: down.x + 1,
-            y: down.y,
+        // the walls catch sand which would otherwise fall into the abyss
+        let config = CaveConfig {
+            walls: Some((493, 504)),
+            ..Default::default()
         };
+        assert!(Cave::new(&rocks, &config).pour() > 24);
+    }
 
-        if sand_points.contains(&down) || rocks.contains(&down) {
-            // the unit of sand attempts to instead move diagonally *one step down and to the left*
-            if sand_points.contains(&down_left) || rocks.contains(&down_left) {
-                // If that tile is blocked, the unit of sand attempts to instead move diagonally
-                // *one step down and to the right*
-                if sand_points.contains(&down_right) || rocks.contains(&down_right) {
-                    //If all three possible destinations are blocked, the unit of sand *comes to
-                    //rest* and no longer moves
-                    return Some(sand);
-                } else {
-                    sand = down_right;
-                }
-            } else {
-                sand = down_left;
-            }
-        } else {
-            sand = down;
-        }
-        if !check_floor && sand.y > rocks.y_max {
-            debug!(
-                "sand reaches out of bounds: {} > {} (y_max)",
-                sand.y, rocks.y_max
-            );
-            return None;
-        }
-        if check_floor && sand.y == floor {
-            debug!("sand hits floor {:?}", sand);
-            return Some(sand_old);
+    #[test]
+    fn large_cave() {
+        // lots of short shelves, scattered pseudo-randomly
+        let mut rocks = Vec::new();
+        for i in 0..20000 {
+            let (x, y) = (400 + (i * 7919) % 200, 2 + (i * 104729) % 100);
+            rocks.push(Rock::new(vec![Point2D { x, y }, Point2D { x: x + 2, y }]));
         }
+        let config = CaveConfig {
+            floor: Some(2),
+            ..Default::default()
+        };
+        let mut simulated = Cave::new(&rocks, &config);
+        let mut filled = simulated.clone();
+        let resumed = simulated.settle(Strategy::Simulate);
+        let naive = pour_naively(&mut Cave::new(&rocks, &config), 1);
+        assert_eq!(naive, resumed);
+        assert_eq!(resumed, filled.settle(Strategy::Fill));
+        assert_eq!(simulated.to_string(), filled.to_string());
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    const DAY: i32 = 14;
 
     #[test]
-    fn example() {
-        let input = b"498,4 -> 498,6 -> 496,6
-503,4 -> 502,4 -> 502,9 -> 494,9
-";
-
-        let solution = solve(input);
-        assert_eq!("24", solution.0, "part 1");
-        assert_eq!("93", solution.1, "part 2");
+    fn analytic_fill() {
+        let rocks = parse_rocks(EXAMPLE);
+        assert_eq!(None, Cave::new(&rocks, &CaveConfig::default()).fill());
+        // falls back to the simulation
+        assert_eq!(
+            24,
+            Cave::new(&rocks, &CaveConfig::default()).settle(Strategy::Fill)
+        );
+
+        let configs = [
+            CaveConfig {
+                floor: Some(2),
+                ..Default::default()
+            },
+            CaveConfig {
+                sources: vec![Point2D { x: 500, y: 0 }, Point2D { x: 497, y: 1 }],
+                floor: Some(2),
+                walls: None,
+            },
+            CaveConfig {
+                sources: vec![Point2D { x: 496, y: 0 }, Point2D { x: 503, y: 0 }],
+                floor: Some(4),
+                walls: Some((493, 506)),
+            },
+            // a source inside of rock never produces any sand
+            CaveConfig {
+                sources: vec![Point2D { x: 498, y: 5 }, Point2D { x: 500, y: 0 }],
+                floor: Some(1),
+                walls: None,
+            },
+        ];
+        for config in configs {
+            let mut simulated = Cave::new(&rocks, &config);
+            let mut filled = simulated.clone();
+            assert_eq!(simulated.pour(), filled.fill().unwrap(), "{config:?}");
+            assert_eq!(simulated.to_string(), filled.to_string());
+        }
+        let config = CaveConfig {
+            floor: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(Some(93), Cave::new(&rocks, &config).fill());
     }
 
     #[test]
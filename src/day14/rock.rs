@@ -1,8 +1,7 @@
 use aoc_lib::point::Point2D;
-use log::trace;
-use std::cmp::{max, min};
 
-#[derive(Debug)]
+/// A rock structure: a path of horizontal and vertical lines.
+#[derive(Debug, Clone)]
 pub struct Rock {
     points: Vec<Point2D>,
     pub y_min: i64,
@@ -40,51 +39,32 @@ impl Rock {
         }
     }
 
-    /// Test whether `candidate` is part of the rock structure.
-    pub fn contains(&self, candidate: &Point2D) -> bool {
-        trace!("checking points: {:?}", self.points);
-        trace!(
-            "top_left=({}, {}), bottom_right({}, {})",
-            self.x_min,
-            self.y_min,
-            self.x_max,
-            self.y_max
-        );
-        if candidate.y < self.y_min
-            || candidate.y > self.y_max
-            || candidate.x < self.x_min
-            || candidate.x > self.x_max
-        {
-            trace!(
-                "rejecting point {:?} because not inside rectangle",
-                candidate
-            );
-            return false;
-        }
-        trace!("point {:?} is inside rectangle", candidate);
-        for (p, q) in self.points.iter().zip(&self.points[1..]) {
-            trace!("checking line {:?} -> {:?}", p, q);
-            if p.x == q.x {
-                let result = candidate.x == p.x
-                    && min(p.y, q.y) <= candidate.y
-                    && candidate.y <= max(p.y, q.y);
-                if result {
-                    trace!("point is on horizontal line: {result}");
-                    return result;
-                }
-            } else if p.y == q.y {
-                let result = candidate.y == p.y
-                    && min(p.x, q.x) <= candidate.x
-                    && candidate.x <= max(p.x, q.x);
-                if result {
-                    trace!("point is on vertical line: {result}");
-                    return result;
-                }
-            } else {
-                panic!("only horizontal and vertical lines are allowed");
-            }
-        }
-        trace!("point {:?} does not hit this rock", candidate);
-        false
+    pub fn points(&self) -> &[Point2D] {
+        &self.points
+    }
+
+    /// All points covered by the rock; corners are visited twice.
+    pub fn cells(&self) -> impl Iterator<Item = Point2D> + '_ {
+        self.points
+            .iter()
+            .zip(self.points.iter().skip(1))
+            .flat_map(|(p, q)| {
+                assert!(
+                    p.x == q.x || p.y == q.y,
+                    "only horizontal and vertical lines are allowed"
+                );
+                let (dx, dy) = ((q.x - p.x).signum(), (q.y - p.y).signum());
+                let steps = (q.x - p.x).abs().max((q.y - p.y).abs());
+                (0..=steps).map(move |i| Point2D {
+                    x: p.x + i * dx,
+                    y: p.y + i * dy,
+                })
+            })
+            .chain(
+                self.points
+                    .first()
+                    .copied()
+                    .filter(|_| self.points.len() == 1),
+            )
     }
 }
@@ -0,0 +1,242 @@
+use aoc_lib::{interval::ClosedInterval, point::Point2D};
+use log::debug;
+
+/// All points within a Manhattan distance of `radius` around `center`.
+///
+/// In coordinates rotated by 45°, `u = x + y` and `v = x - y`, a diamond becomes an
+/// axis-aligned square, which is what the coverage computations below work with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diamond {
+    pub center: Point2D,
+    pub radius: i64,
+}
+
+impl Diamond {
+    pub fn new(center: Point2D, radius: i64) -> Self {
+        Self { center, radius }
+    }
+
+    pub fn contains(&self, p: Point2D) -> bool {
+        self.center.manhattan(p) <= self.radius
+    }
+
+    /// The covered part of row `y`.
+    pub fn row(&self, y: i64) -> Option<ClosedInterval> {
+        let dx = self.radius - (self.center.y - y).abs();
+        (dx >= 0).then(|| ClosedInterval::new(self.center.x - dx, self.center.x + dx))
+    }
+
+    /// The covered part of column `x`.
+    pub fn column(&self, x: i64) -> Option<ClosedInterval> {
+        let dy = self.radius - (self.center.x - x).abs();
+        (dy >= 0).then(|| ClosedInterval::new(self.center.y - dy, self.center.y + dy))
+    }
+
+    /// The range of `x + y`.
+    pub fn u(&self) -> ClosedInterval {
+        let u = self.center.x + self.center.y;
+        ClosedInterval::new(u - self.radius, u + self.radius)
+    }
+
+    /// The range of `x - y`.
+    pub fn v(&self) -> ClosedInterval {
+        let v = self.center.x - self.center.y;
+        ClosedInterval::new(v - self.radius, v + self.radius)
+    }
+}
+
+/// An axis-aligned rectangle in regular coordinates, bounds included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: ClosedInterval,
+    pub y: ClosedInterval,
+}
+
+impl Rect {
+    pub fn new(x: ClosedInterval, y: ClosedInterval) -> Self {
+        assert!(x.a <= x.b && y.a <= y.b, "empty rectangle");
+        Self { x, y }
+    }
+
+    /// `0..=max` in both directions.
+    pub fn square(max: i64) -> Self {
+        Self::new(ClosedInterval::new(0, max), ClosedInterval::new(0, max))
+    }
+
+    pub fn area(&self) -> usize {
+        self.x.len() * self.y.len()
+    }
+
+    pub fn contains(&self, p: Point2D) -> bool {
+        self.x.a <= p.x && p.x <= self.x.b && self.y.a <= p.y && p.y <= self.y.b
+    }
+
+    /// The range of `x + y`.
+    fn u(&self) -> ClosedInterval {
+        ClosedInterval::new(self.x.a + self.y.a, self.x.b + self.y.b)
+    }
+
+    /// The range of `x - y`.
+    fn v(&self) -> ClosedInterval {
+        ClosedInterval::new(self.x.a - self.y.b, self.x.b - self.y.a)
+    }
+}
+
+/// A region without coverage: those points of `rect` whose rotated coordinates lie in `u` × `v`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub u: ClosedInterval,
+    pub v: ClosedInterval,
+    rect: Rect,
+}
+
+impl Gap {
+    /// Clip the box to the rectangle; `None` if no point is left.
+    fn clipped(u: ClosedInterval, v: ClosedInterval, rect: &Rect) -> Option<Self> {
+        let (x, y) = (rect.x, rect.y);
+        // u values for which some v in the box maps to a point inside the rectangle
+        let a = [u.a, v.a + 2 * y.a, 2 * x.a - v.b, x.a + y.a];
+        let b = [u.b, 2 * x.b - v.a, v.b + 2 * y.b, x.b + y.b];
+        let (a, b) = (*a.iter().max().unwrap(), *b.iter().min().unwrap());
+        if a > b {
+            return None;
+        }
+        let gap = Self {
+            u: ClosedInterval::new(a, b),
+            v,
+            rect: *rect,
+        };
+        // the parity of u and v has to match, which can rule out thin boxes
+        let empty = gap.points().next().is_none();
+        (!empty).then_some(gap)
+    }
+
+    /// The number of points in the gap.
+    pub fn area(&self) -> usize {
+        (self.u.a..=self.u.b)
+            .map(|u| {
+                let (lo, hi) = self.window(u);
+                if lo > hi {
+                    0
+                } else {
+                    ((hi - lo) / 2 + 1) as usize
+                }
+            })
+            .sum()
+    }
+
+    /// The points of the gap, ordered by `x + y` and then by `x - y`.
+    pub fn points(&self) -> impl Iterator<Item = Point2D> {
+        let gap = *self;
+        (self.u.a..=self.u.b).flat_map(move |u| {
+            let (lo, hi) = gap.window(u);
+            (lo..=hi).step_by(2).map(move |v| Point2D {
+                x: (u + v) / 2,
+                y: (u - v) / 2,
+            })
+        })
+    }
+
+    /// The first and last `v` of the points with the given `u`; every other `v` in between.
+    fn window(&self, u: i64) -> (i64, i64) {
+        let (v, x, y) = (self.v, self.rect.x, self.rect.y);
+        let lo = v.a.max(2 * x.a - u).max(u - 2 * y.b);
+        let hi = v.b.min(2 * x.b - u).min(u - 2 * y.a);
+        // x and y are only integers if u and v are both even or both odd
+        (lo + (lo - u).rem_euclid(2), hi)
+    }
+}
+
+/// Find all regions of `rect` which none of the diamonds cover.
+pub fn gaps(diamonds: &[Diamond], rect: &Rect) -> Vec<Gap> {
+    let gaps: Vec<Gap> = uncovered_boxes(diamonds, rect.u(), rect.v())
+        .into_iter()
+        .filter_map(|(u, v)| Gap::clipped(u, v, rect))
+        .collect();
+    debug!("{} gaps", gaps.len());
+    gaps
+}
+
+/// Whether every point of `target` lies in one of the diamonds.
+pub fn covers(diamonds: &[Diamond], target: &Diamond) -> bool {
+    // the rotated square of the target holds points of both parities, unless it's a single
+    // cell in u and v which doesn't correspond to a point at all
+    uncovered_boxes(diamonds, target.u(), target.v())
+        .iter()
+        .all(|(u, v)| u.len() == 1 && v.len() == 1 && (u.a - v.a) % 2 != 0)
+}
+
+/// Boxes in rotated coordinates within `u_range` × `v_range` which none of the diamonds cover.
+///
+/// The rotated plane is cut into slabs along the `u` boundaries of the diamonds. Within a slab,
+/// the same diamonds are active, so the uncovered `v` ranges follow from their sorted `v`
+/// intervals. Boxes which continue a box of the previous slab are merged with it.
+fn uncovered_boxes(
+    diamonds: &[Diamond],
+    u_range: ClosedInterval,
+    v_range: ClosedInterval,
+) -> Vec<(ClosedInterval, ClosedInterval)> {
+    let mut edges: Vec<i64> = diamonds
+        .iter()
+        .flat_map(|d| [d.u().a, d.u().b + 1])
+        .chain([u_range.a, u_range.b + 1])
+        .filter(|&u| u_range.a <= u && u <= u_range.b + 1)
+        .collect();
+    edges.sort_unstable();
+    edges.dedup();
+
+    let mut boxes: Vec<(ClosedInterval, ClosedInterval)> = Vec::new();
+    // boxes reaching into the previous slab, by index
+    let mut open: Vec<usize> = Vec::new();
+    let mut intervals = Vec::with_capacity(diamonds.len());
+    for slab in edges.windows(2) {
+        let slab = ClosedInterval::new(slab[0], slab[1] - 1);
+        intervals.clear();
+        intervals.extend(
+            diamonds
+                .iter()
+                .filter(|d| d.u().a <= slab.a && slab.a <= d.u().b)
+                .map(|d| d.v()),
+        );
+        intervals.sort_unstable_by_key(|iv| iv.a);
+
+        let mut uncovered = Vec::new();
+        let mut next = v_range.a;
+        for iv in intervals
+            .iter()
+            .chain([&ClosedInterval::new(v_range.b + 1, v_range.b + 1)])
+        {
+            if iv.a > next {
+                uncovered.push(ClosedInterval::new(next, iv.a.min(v_range.b + 1) - 1));
+            }
+            next = next.max(iv.b + 1);
+            if next > v_range.b {
+                break;
+            }
+        }
+
+        let mut still_open = Vec::with_capacity(uncovered.len());
+        for v in uncovered {
+            match open.iter().find(|&&i| boxes[i].1 == v) {
+                Some(&i) => {
+                    boxes[i].0.b = slab.b;
+                    still_open.push(i);
+                }
+                None => {
+                    still_open.push(boxes.len());
+                    boxes.push((slab, v));
+                }
+            }
+        }
+        open = still_open;
+    }
+
+    boxes
+}
+
+/// Every point of `rect` which none of the diamonds cover, ordered by `y` and then `x`.
+pub fn uncovered(diamonds: &[Diamond], rect: &Rect) -> Vec<Point2D> {
+    let mut points: Vec<Point2D> = gaps(diamonds, rect).iter().flat_map(Gap::points).collect();
+    points.sort_unstable_by_key(|p| (p.y, p.x));
+    points
+}
@@ -1,5 +1,7 @@
+pub mod coverage;
+pub mod query;
+
 use ahash::AHashSet;
-use arrayvec::ArrayVec;
 use log::{debug, trace};
 
 use aoc_lib::{
@@ -8,11 +10,16 @@ use aoc_lib::{
     point::Point2D,
 };
 
-#[derive(Debug)]
-struct Sensor(Point2D);
+use coverage::{Diamond, Rect};
+
+/// The tuning frequency is `x * FREQUENCY_MULTIPLIER + y`.
+const FREQUENCY_MULTIPLIER: i64 = 4000000;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Sensor(pub Point2D);
 
 #[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
-struct Beacon(Point2D);
+pub struct Beacon(pub Point2D);
 
 pub fn solve(input: &[u8]) -> (String, String) {
     let pairings = parse_input(input);
@@ -21,8 +28,8 @@ pub fn solve(input: &[u8]) -> (String, String) {
     (part1.to_string(), part2.to_string())
 }
 
-fn parse_input(input: &[u8]) -> ArrayVec<(Sensor, Beacon), 64> {
-    let mut pairings: ArrayVec<(Sensor, Beacon), 64> = ArrayVec::new();
+pub fn parse_input(input: &[u8]) -> Vec<(Sensor, Beacon)> {
+    let mut pairings = Vec::new();
     let mut input = input;
     while !input.is_empty() {
         let (rest, x) = parse::integer(input, true).unwrap();
@@ -45,20 +52,11 @@ fn count_illegal_beacon_locs(
 ) -> (usize, Vec<ClosedInterval>) {
     let mut intervals: Vec<ClosedInterval> = Vec::with_capacity(256);
     let mut beacons_in_row: AHashSet<Beacon> = AHashSet::new();
-    for (sensor, beacon) in pairings {
+    for ((_, beacon), diamond) in pairings.iter().zip(diamonds(pairings)) {
         if beacon.0.y == y_dest {
             beacons_in_row.insert(*beacon);
         }
-        let dy = (sensor.0.y - y_dest).abs();
-        let dist = sensor.0.manhattan(beacon.0);
-        let dx = dist - dy;
-        if dx < 0 {
-            continue;
-        }
-
-        debug_assert!(dx >= 0);
-        let interval = ClosedInterval::new(sensor.0.x - dx, sensor.0.x + dx);
-        intervals.push(interval);
+        intervals.extend(diamond.row(y_dest));
     }
     trace!(
         "beacons_in_row: {}, intervals: {:?}",
@@ -73,26 +71,21 @@ fn count_illegal_beacon_locs(
     (sum - beacons_in_row.len(), merged)
 }
 
+/// The area each sensor rules out.
+fn diamonds(pairings: &[(Sensor, Beacon)]) -> Vec<Diamond> {
+    pairings
+        .iter()
+        .map(|(sensor, beacon)| Diamond::new(sensor.0, sensor.0.manhattan(beacon.0)))
+        .collect()
+}
+
+/// The frequency of the only point in `0..=max` which no sensor covers.
 fn tuning_frequency(pairings: &[(Sensor, Beacon)], max: usize) -> i64 {
-    let horizontal = ClosedInterval::new(0, max as i64);
-    for y in 0..=max {
-        let non_beacons = count_illegal_beacon_locs(pairings, y as i64).1;
-        // find gap
-        for (lhs, rhs) in non_beacons.iter().zip(non_beacons.iter().skip(1)) {
-            if let (Some(lhs), Some(rhs)) = (lhs.intersect(&horizontal), rhs.intersect(&horizontal))
-            {
-                debug!("y={y}, lhs {:?}, rhs: {:?}", lhs, rhs);
-                if lhs.disjoint(&rhs) {
-                    let gap_width = rhs.a - lhs.b;
-                    debug!("found gap of width {gap_width}");
-                    if gap_width == 2 {
-                        return (y as i64) + 4000000 * (lhs.b + 1);
-                    }
-                }
-            }
-        }
-    }
-    0
+    let uncovered = coverage::uncovered(&diamonds(pairings), &Rect::square(max as i64));
+    debug!("uncovered: {:?}", uncovered);
+    assert_eq!(1, uncovered.len(), "expected a single spot for the beacon");
+    let p = uncovered[0];
+    p.x * FREQUENCY_MULTIPLIER + p.y
 }
 
 #[cfg(test)]
@@ -129,6 +122,82 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3
         assert_eq!(56000011, tuning_frequency(&pairings, 20));
     }
 
+    #[test]
+    fn gaps() {
+        use coverage::Gap;
+
+        let diamonds = diamonds(&parse_input(EXAMPLE));
+        let gaps = coverage::gaps(&diamonds, &Rect::square(20));
+        assert_eq!(1, gaps.len());
+        assert_eq!(
+            vec![Point2D::new(14, 11)],
+            gaps[0].points().collect::<Vec<_>>()
+        );
+
+        // a larger area has uncovered spots all around the sensors
+        let rect = Rect::new(ClosedInterval::new(-8, 30), ClosedInterval::new(-5, 27));
+        let naive: Vec<Point2D> = (rect.y.a..=rect.y.b)
+            .flat_map(|y| (rect.x.a..=rect.x.b).map(move |x| Point2D::new(x, y)))
+            .filter(|&p| !diamonds.iter().any(|d| d.contains(p)))
+            .collect();
+        assert_eq!(naive, coverage::uncovered(&diamonds, &rect));
+        let gaps = coverage::gaps(&diamonds, &rect);
+        let total: usize = gaps.iter().map(|g: &Gap| g.points().count()).sum();
+        assert_eq!(naive.len(), total);
+
+        // without sensors, the whole rectangle is one gap
+        let gaps = coverage::gaps(&[], &rect);
+        assert_eq!(1, gaps.len());
+        assert_eq!(39 * 33, gaps[0].points().count());
+    }
+
+    #[test]
+    fn queries() {
+        use query::Field;
+
+        let field = Field::new(parse_input(EXAMPLE));
+        let p = Point2D::new;
+        assert_eq!(vec![ClosedInterval::new(-2, 24)], field.row(10));
+        assert_eq!(
+            vec![ClosedInterval::new(-3, 13), ClosedInterval::new(15, 25)],
+            field.row(11)
+        );
+        assert_eq!(
+            field.row(11),
+            count_illegal_beacon_locs(field.pairings(), 11).1
+        );
+        assert_eq!(vec![ClosedInterval::new(-6, 23)], field.column(20));
+        assert!(field.column(-100).is_empty());
+
+        // (2, 10) is the closest beacon of three sensors
+        assert_eq!(vec![6, 7, 8], field.sensors_covering(p(2, 10)));
+        assert!(field.sensors_covering(p(14, 11)).is_empty());
+
+        // everything but the distress beacon
+        assert_eq!(21 * 21 - 1, field.covered_area(&Rect::square(20)));
+        let rect = Rect::new(ClosedInterval::new(-8, 30), ClosedInterval::new(-5, 27));
+        let naive = (rect.y.a..=rect.y.b)
+            .flat_map(|y| (rect.x.a..=rect.x.b).map(move |x| p(x, y)))
+            .filter(|&q| !field.sensors_covering(q).is_empty())
+            .count();
+        assert_eq!(naive, field.covered_area(&rect));
+
+        // e.g. (14, 3) only reaches its beacon at (15, 3), which (13, 2) covers as well
+        assert_eq!(vec![5, 12], field.redundant());
+        let d = field.diamonds()[5];
+        for y in d.center.y - d.radius..=d.center.y + d.radius {
+            for x in d.center.x - d.radius..=d.center.x + d.radius {
+                if d.contains(p(x, y)) {
+                    assert!(field.sensors_covering(p(x, y)).len() > 1);
+                }
+            }
+        }
+        let mut pairings = parse_input(EXAMPLE);
+        pairings.push(pairings[6]);
+        let field = Field::new(pairings);
+        assert_eq!(vec![5, 6, 12, 14], field.redundant());
+    }
+
     #[test]
     fn part1_and_part2() {
         let answer = solve(&aoc_lib::io::read_input(DAY).unwrap());
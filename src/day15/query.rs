@@ -0,0 +1,75 @@
+use aoc_lib::{
+    interval::{merge_intervals, ClosedInterval},
+    point::Point2D,
+};
+
+use super::coverage::{self, Diamond, Rect};
+use super::{Beacon, Sensor};
+
+/// Answers questions about the area the sensors cover.
+///
+/// A sensor covers every point at most as far away as its closest beacon, including the beacon
+/// itself.
+#[derive(Debug)]
+pub struct Field {
+    pairings: Vec<(Sensor, Beacon)>,
+    diamonds: Vec<Diamond>,
+}
+
+impl Field {
+    pub fn new(pairings: Vec<(Sensor, Beacon)>) -> Self {
+        let diamonds = super::diamonds(&pairings);
+        Self { pairings, diamonds }
+    }
+
+    pub fn pairings(&self) -> &[(Sensor, Beacon)] {
+        &self.pairings
+    }
+
+    pub fn diamonds(&self) -> &[Diamond] {
+        &self.diamonds
+    }
+
+    /// The number of points in `rect` which at least one sensor covers.
+    pub fn covered_area(&self, rect: &Rect) -> usize {
+        let uncovered: usize = coverage::gaps(&self.diamonds, rect)
+            .iter()
+            .map(|g| g.area())
+            .sum();
+        rect.area() - uncovered
+    }
+
+    /// The covered parts of row `y`, merged.
+    pub fn row(&self, y: i64) -> Vec<ClosedInterval> {
+        let intervals: Vec<_> = self.diamonds.iter().filter_map(|d| d.row(y)).collect();
+        merge_intervals(&intervals)
+    }
+
+    /// The covered parts of column `x`, merged.
+    pub fn column(&self, x: i64) -> Vec<ClosedInterval> {
+        let intervals: Vec<_> = self.diamonds.iter().filter_map(|d| d.column(x)).collect();
+        merge_intervals(&intervals)
+    }
+
+    /// Indices of the sensors which cover `p`.
+    pub fn sensors_covering(&self, p: Point2D) -> Vec<usize> {
+        (0..self.diamonds.len())
+            .filter(|&i| self.diamonds[i].contains(p))
+            .collect()
+    }
+
+    /// Indices of the sensors whose area is covered by the other sensors anyway.
+    ///
+    /// Each sensor is checked on its own: of two identical sensors both are redundant, but
+    /// only one of them can go.
+    pub fn redundant(&self) -> Vec<usize> {
+        let mut others = Vec::with_capacity(self.diamonds.len());
+        (0..self.diamonds.len())
+            .filter(|&i| {
+                others.clear();
+                others.extend(self.diamonds[..i].iter().chain(&self.diamonds[i + 1..]));
+                coverage::covers(&others, &self.diamonds[i])
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,96 @@
+use binary_heap_plus::{BinaryHeap, MinComparator};
+use log::debug;
+
+use super::Network;
+
+const UNREACHABLE: i32 = i32::MAX / 4;
+
+/// Travel times between a few key valves of a network, e.g. those worth opening.
+///
+/// Computed with one Dijkstra per key valve on the sparse tunnel graph, so large networks with
+/// only a handful of interesting valves stay cheap.
+#[derive(Debug, Clone)]
+pub struct Compressed {
+    /// network index of each key valve
+    keys: Vec<usize>,
+    /// between key valves
+    dist: Vec<Vec<i32>>,
+    /// per key valve: the predecessor of every valve on a shortest way from the key valve
+    parents: Vec<Vec<usize>>,
+}
+
+impl Compressed {
+    pub fn new(network: &Network, keys: Vec<usize>) -> Self {
+        let mut dist = Vec::with_capacity(keys.len());
+        let mut parents = Vec::with_capacity(keys.len());
+        for &key in &keys {
+            let (all, parent) = dijkstra(network, key);
+            dist.push(keys.iter().map(|&k| all[k]).collect());
+            parents.push(parent);
+        }
+        debug!("compressed {} valves to {}", network.len(), keys.len());
+        Self {
+            keys,
+            dist,
+            parents,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The network index of key valve `k`.
+    pub fn valve(&self, k: usize) -> usize {
+        self.keys[k]
+    }
+
+    /// Which key valve the network valve `idx` is, if any.
+    pub fn key(&self, idx: usize) -> Option<usize> {
+        self.keys.iter().position(|&k| k == idx)
+    }
+
+    /// Minutes it takes to get from key valve `a` to key valve `b`; `None` if there's no way.
+    pub fn distance(&self, a: usize, b: usize) -> Option<i32> {
+        Some(self.dist[a][b]).filter(|&d| d < UNREACHABLE)
+    }
+
+    /// The network valves on a shortest way from key valve `a` to key valve `b`, both
+    /// included.
+    pub fn path(&self, a: usize, b: usize) -> Option<Vec<usize>> {
+        self.distance(a, b)?;
+        let (from, parent) = (self.keys[a], &self.parents[a]);
+        let mut path = vec![self.keys[b]];
+        while *path.last().unwrap() != from {
+            path.push(parent[*path.last().unwrap()]);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+fn dijkstra(network: &Network, source: usize) -> (Vec<i32>, Vec<usize>) {
+    let mut dist = vec![UNREACHABLE; network.len()];
+    let mut parent = vec![usize::MAX; network.len()];
+    let mut queue: BinaryHeap<(i32, usize), MinComparator> = BinaryHeap::with_capacity_min(64);
+    dist[source] = 0;
+    parent[source] = source;
+    queue.push((0, source));
+    while let Some((d, u)) = queue.pop() {
+        if d > dist[u] {
+            continue;
+        }
+        for &(v, w) in network.tunnels(u) {
+            if d + w < dist[v] {
+                dist[v] = d + w;
+                parent[v] = u;
+                queue.push((d + w, v));
+            }
+        }
+    }
+    (dist, parent)
+}
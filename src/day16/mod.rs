@@ -1,169 +1,147 @@
+pub mod graph;
+pub mod plan;
+
 use ahash::AHashMap;
 use log::debug;
 use std::fmt::Display;
 
-use aoc_lib::graph::AdjacencyMatrix;
-use aoc_lib::{bitset::Bitset, parse};
-
-const MAX_VALVE: usize = 26 * 26;
+use plan::{Agent, Optimizer, Strategy};
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-struct Valve((char, char));
+/// A valve, identified by its name.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Valve(pub String);
 
-impl Valve {
-    pub fn encode(&self) -> usize {
-        let unwrapped = self.0;
-        let low = (unwrapped.1 as u8 - b'A') as usize;
-        let high = (unwrapped.0 as u8 - b'A') as usize * 26;
-        low + high
+impl From<&str> for Valve {
+    fn from(name: &str) -> Self {
+        Valve(name.to_string())
     }
 }
 
 impl Display for Valve {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}{}", self.0 .0, self.0 .1)
+        write!(f, "{}", self.0)
     }
 }
 
-pub fn solve(input: &[u8]) -> (String, String) {
-    let mut input = input;
-    let mut flow_rates: AHashMap<Valve, i32> = AHashMap::with_capacity(16);
-    // one-based graph; graph[1] gives the edges of AA
-    let mut graph: AdjacencyMatrix<MAX_VALVE> = AdjacencyMatrix::new();
-
-    while !input.is_empty() {
-        let valve = Valve((input[6] as char, input[7] as char));
-        let (rest, rate) = parse::positive(&input[23..], false).unwrap();
-        if rate > 0 {
-            flow_rates.insert(valve, rate as i32);
-        }
+/// The valves and the tunnels between them; valves are numbered in order of appearance.
+#[derive(Debug, Clone, Default)]
+pub struct Network {
+    valves: Vec<Valve>,
+    ids: AHashMap<Valve, usize>,
+    rates: Vec<u32>,
+    /// per valve: neighbor and the minutes it takes to get there
+    tunnels: Vec<Vec<(usize, i32)>>,
+}
 
-        let first_upper = rest
-            .iter()
-            .enumerate()
-            .find(|x| (*x.1 as char).is_uppercase())
-            .unwrap()
-            .0;
-        input = &rest[first_upper..];
-        while input[0] != b'\n' {
-            let other = Valve((input[0] as char, input[1] as char));
-            graph.add_edge_undirected(valve.encode(), other.encode(), 1);
-            input = &input[2..];
-            if input[0] == b',' {
-                input = &input[2..];
-            }
-        }
-        input = parse::seek_next_line(input);
+impl Network {
+    pub fn len(&self) -> usize {
+        self.valves.len()
     }
 
-    let dist = graph.floyd_warshall();
+    pub fn is_empty(&self) -> bool {
+        self.valves.is_empty()
+    }
 
-    // enumerate non-zero flow rates for bitset
-    let mut valve_to_bitset_index: AHashMap<Valve, usize> = AHashMap::with_capacity(16);
-    for (i, &valve) in flow_rates.keys().enumerate() {
-        valve_to_bitset_index.insert(valve, i);
+    pub fn valve(&self, idx: usize) -> &Valve {
+        &self.valves[idx]
     }
 
-    let facts = Facts {
-        dist,
-        valve_to_bitset_index,
-        flow_rates,
-    };
+    pub fn index(&self, valve: &Valve) -> Option<usize> {
+        self.ids.get(valve).copied()
+    }
 
-    let start = Valve(('A', 'A'));
+    pub fn rate(&self, idx: usize) -> u32 {
+        self.rates[idx]
+    }
 
-    let mut cache = AHashMap::with_capacity(1024);
-    let part1 = calc_pressure(&facts, &mut cache, start, 30, Bitset::new(), 0);
+    /// The neighbors of a valve and how many minutes it takes to get there.
+    pub fn tunnels(&self, idx: usize) -> &[(usize, i32)] {
+        &self.tunnels[idx]
+    }
 
-    let mut cache = AHashMap::with_capacity(1024);
-    let part2 = calc_pressure(&facts, &mut cache, start, 26, Bitset::new(), 1);
+    /// Add a valve, or update the flow rate of a known one.
+    pub fn add_valve(&mut self, valve: &Valve, rate: u32) -> usize {
+        let idx = self.id(valve);
+        self.rates[idx] = rate;
+        idx
+    }
 
-    (part1.to_string(), part2.to_string())
+    /// Connect two valves in both directions; of several tunnels, the fastest one counts.
+    pub fn add_tunnel(&mut self, a: &Valve, b: &Valve, minutes: i32) {
+        assert!(minutes > 0, "tunnels take time");
+        let (a, b) = (self.id(a), self.id(b));
+        for (from, to) in [(a, b), (b, a)] {
+            match self.tunnels[from].iter_mut().find(|(n, _)| *n == to) {
+                Some((_, w)) => *w = (*w).min(minutes),
+                None => self.tunnels[from].push((to, minutes)),
+            }
+        }
+    }
+
+    fn id(&mut self, valve: &Valve) -> usize {
+        if let Some(&idx) = self.ids.get(valve) {
+            return idx;
+        }
+        self.valves.push(valve.clone());
+        self.ids.insert(valve.clone(), self.valves.len() - 1);
+        self.rates.push(0);
+        self.tunnels.push(Vec::new());
+        self.valves.len() - 1
+    }
 }
 
-struct Facts {
-    dist: [[i32; MAX_VALVE]; MAX_VALVE],
-    // TODO: get rid of this, substracting a constant should be enough
-    valve_to_bitset_index: AHashMap<Valve, usize>,
-    // TODO: use array
-    flow_rates: AHashMap<Valve, i32>,
+pub fn solve(input: &[u8]) -> (String, String) {
+    let network = parse_input(input);
+    let start = Valve::from("AA");
+
+    let part1 = Optimizer::new(&network, vec![Agent::new(start.clone(), 30)])
+        .optimize()
+        .pressure;
+    let part2 = Optimizer::new(&network, vec![Agent::new(start, 26); 2])
+        .with_strategy(Strategy::Subsets)
+        .optimize()
+        .pressure;
+
+    (part1.to_string(), part2.to_string())
 }
 
-// Dynamic programming approach
-fn calc_pressure(
-    facts: &Facts,
-    // cached solutions
-    cache: &mut AHashMap<(Valve, i32, Bitset, u16), i32>,
-    // cache these
-    start: Valve,
-    minutes_left: i32,
-    open_valves: Bitset,
-    players_remaining: u16, // 1 then 0
-) -> i32 {
-    debug!(
-        "solving: {start}, {minutes_left}, open_valves: {:?}",
-        open_valves
-    );
-
-    let mut best = 0;
-
-    // instead of visiting direct neighbors, we just go directly to valves with flow rate
-    // > 0 and which haven't been opened yet; this is much, much faster.
-    for (&dest, &idx) in facts
-        .valve_to_bitset_index
-        .iter()
-        .filter(|x| !open_valves.is_set(*x.1))
-    {
-        // it takes 1 minute to open the valve after getting there
-        let duration = facts.dist[start.encode()][dest.encode()] + 1;
-        let new_minutes_left = minutes_left - duration;
-        if new_minutes_left > 0 {
-            let rate = facts.flow_rates.get(&dest).unwrap();
-            let pressure = new_minutes_left * rate;
-            let mut new_open_valves = open_valves;
-            new_open_valves.set(idx);
-
-            let cache_key = (dest, new_minutes_left, new_open_valves, players_remaining);
-            let val = if let Some(cached_value) = cache.get(&cache_key) {
-                pressure + cached_value
-            } else {
-                let pressure_subproblem = calc_pressure(
-                    facts,
-                    cache,
-                    dest,
-                    new_minutes_left,
-                    new_open_valves,
-                    players_remaining,
-                );
-                cache.insert(cache_key, pressure_subproblem);
-                pressure + pressure_subproblem
-            };
-            if val > best {
-                best = val;
-            }
+/// Parse lines like `Valve AA has flow rate=0; tunnels lead to valves DD, II:3`.
+///
+/// A name is a single word without commas or colons. A tunnel takes one minute unless its
+/// duration follows the name after a colon.
+pub fn parse_input(input: &[u8]) -> Network {
+    let text = String::from_utf8_lossy(input);
+    let mut network = Network::default();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let (name, rate, tunnels) =
+            parse_line(line).unwrap_or_else(|| panic!("malformed line: {line}"));
+        let valve = Valve::from(name);
+        network.add_valve(&valve, rate);
+        for (other, minutes) in tunnels {
+            network.add_tunnel(&valve, &Valve::from(other), minutes);
         }
     }
+    debug!("{} valves", network.len());
+    network
+}
 
-    if players_remaining > 0 {
-        // now it is the next player's turn
-        let best_other_player = calc_pressure(
-            facts,
-            cache,
-            Valve(('A', 'A')),
-            26,
-            open_valves,
-            players_remaining - 1,
-        );
-        if best_other_player > best {
-            best = best_other_player;
-        }
-    }
+/// name, flow rate and tunnels with their durations
+type Line<'a> = (&'a str, u32, Vec<(&'a str, i32)>);
 
-    debug!(
-        "{start}, {minutes_left}, {:?} has max pressure {best}",
-        open_valves
-    );
-    best
+fn parse_line(line: &str) -> Option<Line<'_>> {
+    let (name, rest) = line.strip_prefix("Valve ")?.split_once(' ')?;
+    let (rate, rest) = rest.strip_prefix("has flow rate=")?.split_once(';')?;
+    // skip "tunnel(s) lead(s) to valve(s)"
+    let mut words = rest.split_whitespace();
+    words.by_ref().take(4).count();
+    let mut tunnels = Vec::new();
+    for other in words.flat_map(|w| w.split(',')).filter(|s| !s.is_empty()) {
+        tunnels.push(match other.split_once(':') {
+            Some((other, minutes)) => (other, minutes.parse().ok()?),
+            None => (other, 1),
+        });
+    }
+    Some((name, rate.parse().ok()?, tunnels))
 }
 
 #[cfg(test)]
@@ -172,9 +150,7 @@ mod tests {
 
     const DAY: i32 = 16;
 
-    #[test]
-    fn example() {
-        let input = b"Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+    const EXAMPLE: &[u8] = b"Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
 Valve BB has flow rate=13; tunnels lead to valves CC, AA
 Valve CC has flow rate=2; tunnels lead to valves DD, BB
 Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
@@ -186,7 +162,9 @@ Valve II has flow rate=0; tunnels lead to valves AA, JJ
 Valve JJ has flow rate=21; tunnel leads to valve II
 ";
 
-        let answer = solve(input);
+    #[test]
+    fn example() {
+        let answer = solve(EXAMPLE);
         assert_eq!("1651", answer.0, "part 1");
         assert_eq!(
             "1707", answer.1,
@@ -195,6 +173,189 @@ Valve JJ has flow rate=21; tunnel leads to valve II
         );
     }
 
+    /// Replay a schedule: checks that every agent only uses tunnels and never does two things
+    /// at once, and returns the pressure released.
+    fn replay(network: &Network, agents: &[Agent], schedule: &plan::Schedule) -> u32 {
+        use plan::Action;
+
+        let mut pressure = 0;
+        for (i, agent) in agents.iter().enumerate() {
+            let (mut pos, mut minute) = (agent.start.clone(), 1);
+            for step in schedule.steps.iter().filter(|s| s.agent == i) {
+                assert!(step.minute >= minute, "agent {i} is busy at {step:?}");
+                minute = step.minute + step.duration;
+                assert!(minute <= agent.minutes + 1, "agent {i} runs out of time");
+                match &step.action {
+                    Action::Move { from, to } => {
+                        assert_eq!(&pos, from);
+                        let (a, b) = (network.index(from).unwrap(), network.index(to).unwrap());
+                        assert!(network.tunnels(a).contains(&(b, step.duration)));
+                        pos = to.clone();
+                    }
+                    Action::Open(valve) => {
+                        assert_eq!(&pos, valve);
+                        let rate = network.rate(network.index(valve).unwrap());
+                        pressure += rate * (agent.minutes + 1 - minute) as u32;
+                    }
+                }
+            }
+        }
+        pressure
+    }
+
+    #[test]
+    fn schedules() {
+        use plan::Action;
+
+        let network = parse_input(EXAMPLE);
+        let v = Valve::from;
+        let alone = vec![Agent::new(v("AA"), 30)];
+        let schedule = Optimizer::new(&network, alone.clone()).optimize();
+        assert_eq!(1651, schedule.pressure);
+        assert_eq!(1651, replay(&network, &alone, &schedule));
+        let opened: Vec<Valve> = schedule
+            .steps
+            .iter()
+            .filter_map(|s| match &s.action {
+                Action::Open(valve) => Some(valve.clone()),
+                Action::Move { .. } => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![v("DD"), v("BB"), v("JJ"), v("HH"), v("EE"), v("CC")],
+            opened
+        );
+        assert!(schedule
+            .to_string()
+            .starts_with("minute 1: agent 0 moves from AA to DD\nminute 2: agent 0 opens DD\n"));
+
+        let pair = vec![Agent::new(v("AA"), 26); 2];
+        let schedule = Optimizer::new(&network, pair.clone()).optimize();
+        assert_eq!(1707, schedule.pressure);
+        assert_eq!(1707, replay(&network, &pair, &schedule));
+
+        // a third agent starting right next to HH helps
+        let trio = vec![
+            Agent::new(v("AA"), 26),
+            Agent::new(v("AA"), 26),
+            Agent::new(v("GG"), 26),
+        ];
+        let schedule = Optimizer::new(&network, trio.clone()).optimize();
+        assert!(schedule.pressure > 1707);
+        assert_eq!(schedule.pressure, replay(&network, &trio, &schedule));
+
+        // slower valves release less
+        let slow = Optimizer::new(&network, alone.clone())
+            .with_opening(2)
+            .with_opening_of(&v("DD"), 5)
+            .optimize();
+        assert!(slow.pressure < 1651);
+        let durations: Vec<i32> = slow
+            .steps
+            .iter()
+            .filter(|s| matches!(s.action, Action::Open(_)))
+            .map(|s| s.duration)
+            .collect();
+        assert!(durations.iter().all(|&d| d == 2 || d == 5));
+
+        // no time, no pressure
+        let schedule = Optimizer::new(&network, vec![Agent::new(v("JJ"), 1)]).optimize();
+        assert_eq!(0, schedule.pressure);
+        assert!(schedule.steps.is_empty());
+    }
+
+    #[test]
+    fn weighted_and_large_networks() {
+        use plan::Action;
+
+        let network = parse_input(
+            b"Valve Start has flow rate=0; tunnels lead to valves Left:2, Right:5
+Valve Left has flow rate=10; tunnel leads to valve Start:2
+Valve Right has flow rate=50; tunnels lead to valves Start:5, Left:1
+",
+        );
+        assert_eq!(3, network.len());
+        let agents = vec![Agent::new(Valve::from("Start"), 10)];
+        let schedule = Optimizer::new(&network, agents.clone()).optimize();
+        // the detour via Left is faster than the direct tunnel
+        assert_eq!(6 * 50 + 4 * 10, schedule.pressure);
+        assert_eq!(schedule.pressure, replay(&network, &agents, &schedule));
+        assert_eq!(
+            Action::Move {
+                from: Valve::from("Left"),
+                to: Valve::from("Right")
+            },
+            schedule.steps[1].action
+        );
+        assert_eq!(
+            (3, 1),
+            (schedule.steps[1].minute, schedule.steps[1].duration)
+        );
+
+        // a long corridor with two valves worth opening
+        let mut input = String::new();
+        for i in 0..3000_usize {
+            let rate = match i {
+                1000 => 7,
+                2500 => 3,
+                _ => 0,
+            };
+            input.push_str(&format!(
+                "Valve V{i} has flow rate={rate}; tunnels lead to valves "
+            ));
+            let neighbors: Vec<String> = [i.checked_sub(1), Some(i + 1).filter(|&j| j < 3000)]
+                .into_iter()
+                .flatten()
+                .map(|j| format!("V{j}"))
+                .collect();
+            input.push_str(&neighbors.join(", "));
+            input.push('\n');
+        }
+        let network = parse_input(input.as_bytes());
+        assert_eq!(3000, network.len());
+        let agents = vec![Agent::new(Valve::from("V0"), 3000)];
+        let schedule = Optimizer::new(&network, agents.clone()).optimize();
+        assert_eq!(1999 * 7 + 498 * 3, schedule.pressure);
+        assert_eq!(schedule.pressure, replay(&network, &agents, &schedule));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "malformed line: Valve BB has flow rate=0; tunnel leads to valve AA:x"
+    )]
+    fn malformed_duration() {
+        parse_input(
+            b"Valve AA has flow rate=0; tunnel leads to valve BB
+Valve BB has flow rate=0; tunnel leads to valve AA:x
+",
+        );
+    }
+
+    #[test]
+    fn strategies() {
+        let network = parse_input(EXAMPLE);
+        let v = Valve::from;
+        let setups = [
+            vec![Agent::new(v("AA"), 30)],
+            vec![Agent::new(v("AA"), 26); 2],
+            vec![Agent::new(v("JJ"), 12), Agent::new(v("HH"), 20)],
+            vec![Agent::new(v("EE"), 3), Agent::new(v("AA"), 30)],
+        ];
+        for agents in setups {
+            for opening in [1, 3] {
+                let recursive = Optimizer::new(&network, agents.clone())
+                    .with_opening(opening)
+                    .optimize();
+                let subsets = Optimizer::new(&network, agents.clone())
+                    .with_opening(opening)
+                    .with_strategy(Strategy::Subsets)
+                    .optimize();
+                assert_eq!(recursive.pressure, subsets.pressure, "{agents:?}");
+                assert_eq!(subsets.pressure, replay(&network, &agents, &subsets));
+            }
+        }
+    }
+
     #[test]
     fn part1_and_part2() {
         let answer = solve(&aoc_lib::io::read_input(DAY).unwrap());
@@ -0,0 +1,382 @@
+use std::fmt;
+
+use ahash::AHashMap;
+use log::debug;
+
+use super::graph::Compressed;
+use super::{Network, Valve};
+
+/// Someone who walks through the tunnels and opens valves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Agent {
+    pub start: Valve,
+    /// time budget
+    pub minutes: i32,
+}
+
+impl Agent {
+    pub fn new(start: Valve, minutes: i32) -> Self {
+        Self { start, minutes }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Move { from: Valve, to: Valve },
+    Open(Valve),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub agent: usize,
+    /// the minute the action starts, beginning with 1
+    pub minute: i32,
+    /// how many minutes the action takes
+    pub duration: i32,
+    pub action: Action,
+}
+
+/// What everyone does, ordered by minute and then by agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    /// total pressure released by the opened valves
+    pub pressure: u32,
+    pub steps: Vec<Step>,
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            write!(f, "minute {}: agent {} ", step.minute, step.agent)?;
+            match &step.action {
+                Action::Move { from, to } => writeln!(f, "moves from {from} to {to}")?,
+                Action::Open(valve) => writeln!(f, "opens {valve}")?,
+            }
+        }
+        writeln!(f, "pressure released: {}", self.pressure)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Memoized recursion over the agents, one after another.
+    Recursive,
+    /// Find the best pressure of one agent for every subset of valves, then combine two
+    /// disjoint subsets; at most two agents. Takes time in proportion to valves × minutes ×
+    /// subsets, however many routes there are.
+    Subsets,
+}
+
+/// The subset strategy keeps an entry per subset of valves and, for the widest layer of 16
+/// choose 8 subsets, a pressure per valve and minute left: about 25 MB for 26 minutes.
+const MAX_SUBSET_TARGETS: usize = 16;
+
+/// States of the subset strategy with the same number of opened valves: (opened valves,
+/// position) -> best pressure per minutes left.
+type Layer = AHashMap<(usize, usize), Vec<u32>>;
+
+/// What an agent does next in the best solution of a subproblem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Choice {
+    /// open the given target next
+    Open(usize),
+    /// stop and leave the rest to the next agent
+    Done,
+}
+
+/// Finds the schedule which releases the most pressure.
+///
+/// With the recursive strategy, the agents plan one after another: each one picks its route
+/// among the valves the previous ones left closed. Only valves with a positive flow rate are
+/// worth visiting, so routes jump directly between them on the compressed graph. Subproblems
+/// are cached by agent, position, time left and the set of opened valves.
+#[derive(Debug, Clone)]
+pub struct Optimizer<'a> {
+    network: &'a Network,
+    /// Key valves: first those with a positive flow rate, then the remaining starts. Bit `i` of
+    /// a mask refers to key valve `i`.
+    graph: Compressed,
+    /// number of valves with a positive flow rate
+    targets: usize,
+    agents: Vec<Agent>,
+    /// key valve of each agent's start
+    starts: Vec<usize>,
+    /// minutes it takes to open each target
+    opening: Vec<i32>,
+    strategy: Strategy,
+    /// the last agent who plans a route; those after it stay idle
+    last: usize,
+    cache: AHashMap<(usize, usize, i32, u64), (u32, Choice)>,
+}
+
+impl<'a> Optimizer<'a> {
+    pub fn new(network: &'a Network, agents: Vec<Agent>) -> Self {
+        assert!(!agents.is_empty(), "need at least one agent");
+        let mut keys: Vec<usize> = (0..network.len())
+            .filter(|&i| network.rate(i) > 0)
+            .collect();
+        let targets = keys.len();
+        assert!(targets <= 64, "too many valves with a flow rate");
+        for agent in &agents {
+            let start = network
+                .index(&agent.start)
+                .unwrap_or_else(|| panic!("unknown start {}", agent.start));
+            if !keys.contains(&start) {
+                keys.push(start);
+            }
+        }
+        let graph = Compressed::new(network, keys);
+        let starts = agents
+            .iter()
+            .map(|a| graph.key(network.index(&a.start).unwrap()).unwrap())
+            .collect();
+        Self {
+            network,
+            graph,
+            targets,
+            last: agents.len() - 1,
+            agents,
+            starts,
+            opening: vec![1; targets],
+            strategy: Strategy::Recursive,
+            cache: AHashMap::with_capacity(1024),
+        }
+    }
+
+    /// Opening any valve takes the given number of minutes instead of one.
+    pub fn with_opening(mut self, minutes: i32) -> Self {
+        self.opening.fill(minutes);
+        self.cache.clear();
+        self
+    }
+
+    /// Opening `valve` takes the given number of minutes.
+    pub fn with_opening_of(mut self, valve: &Valve, minutes: i32) -> Self {
+        let idx = self.network.index(valve).expect("unknown valve");
+        // valves without flow are never opened anyway
+        if let Some(t) = self.graph.key(idx).filter(|&t| t < self.targets) {
+            self.opening[t] = minutes;
+            self.cache.clear();
+        }
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn optimize(&mut self) -> Schedule {
+        let mut steps = Vec::new();
+        let pressure = match self.strategy {
+            Strategy::Recursive => {
+                let pressure = self.replay(0, 0, &mut steps);
+                debug!("{} subproblems", self.cache.len());
+                pressure
+            }
+            Strategy::Subsets => self.combine_subsets(&mut steps),
+        };
+        steps.sort_by_key(|s| (s.minute, s.agent));
+        Schedule { pressure, steps }
+    }
+
+    /// Let every agent from `first` to `last` pick the best route among the valves not yet
+    /// `open`; records their steps and returns the pressure they release.
+    fn replay(&mut self, first: usize, open: u64, steps: &mut Vec<Step>) -> u32 {
+        let (mut agent, mut pos, mut minutes, mut open) =
+            (first, self.starts[first], self.agents[first].minutes, open);
+        let pressure = self.best(agent, pos, minutes, open).0;
+        loop {
+            match self.best(agent, pos, minutes, open).1 {
+                Choice::Open(t) => {
+                    let elapsed = self.agents[agent].minutes - minutes;
+                    let arrival = self.walk(agent, elapsed, pos, t, steps);
+                    steps.push(Step {
+                        agent,
+                        minute: arrival + 1,
+                        duration: self.opening[t],
+                        action: Action::Open(self.network.valve(self.graph.valve(t)).clone()),
+                    });
+                    minutes = self.agents[agent].minutes - arrival - self.opening[t];
+                    pos = t;
+                    open |= 1 << t;
+                }
+                Choice::Done if agent < self.last => {
+                    agent += 1;
+                    pos = self.starts[agent];
+                    minutes = self.agents[agent].minutes;
+                }
+                Choice::Done => break,
+            }
+        }
+        pressure
+    }
+
+    /// Split the valves between (at most) two agents, using the best pressure per subset.
+    fn combine_subsets(&mut self, steps: &mut Vec<Step>) -> u32 {
+        assert!(
+            self.agents.len() <= 2,
+            "the subset strategy supports at most two agents"
+        );
+        assert!(
+            self.targets <= MAX_SUBSET_TARGETS,
+            "too many valves for the subset strategy"
+        );
+        let full = (1 << self.targets) - 1;
+        let first = self.released_per_subset(0);
+        let masks = if self.agents.len() == 1 {
+            let best = (0..=full).max_by_key(|&m| first[m]).unwrap();
+            vec![best]
+        } else {
+            let second = best_of_subsets(self.released_per_subset(1));
+            let (_, a) = (0..=full)
+                .map(|a| (first[a] + second[full ^ a].0, a))
+                .max_by_key(|&(p, _)| p)
+                .unwrap();
+            vec![a, second[full ^ a].1]
+        };
+
+        // only the agent at hand plans, and only with its own valves
+        let mut pressure = 0;
+        for (agent, mask) in masks.into_iter().enumerate() {
+            self.last = agent;
+            self.cache.clear();
+            pressure += self.replay(agent, (full ^ mask) as u64, steps);
+        }
+        self.last = self.agents.len() - 1;
+        self.cache.clear();
+        pressure
+    }
+
+    /// The most pressure `agent` releases by opening exactly the valves of each subset; zero
+    /// for subsets it can't open in time.
+    ///
+    /// Dynamic programming over (opened valves, position, minutes left), one layer per number
+    /// of opened valves; only the current layer and the next one are kept.
+    fn released_per_subset(&self, agent: usize) -> Vec<u32> {
+        let minutes = self.agents[agent].minutes.max(0) as usize;
+        let mut released = vec![0; 1 << self.targets];
+        let mut layer = Layer::new();
+        self.open_next(0, self.starts[agent], minutes, 0, minutes, &mut layer);
+        while !layer.is_empty() {
+            let mut next = Layer::new();
+            for ((open, pos), by_left) in layer {
+                // every opened valve adds pressure, so zero means unreachable
+                for (left, &pressure) in by_left.iter().enumerate().filter(|(_, &p)| p > 0) {
+                    released[open] = released[open].max(pressure);
+                    self.open_next(open, pos, left, pressure, minutes, &mut next);
+                }
+            }
+            layer = next;
+        }
+        released
+    }
+
+    /// Add the states reached by opening one more valve to `next`.
+    fn open_next(
+        &self,
+        open: usize,
+        pos: usize,
+        left: usize,
+        pressure: u32,
+        minutes: usize,
+        next: &mut Layer,
+    ) {
+        for t in (0..self.targets).filter(|t| open & (1 << t) == 0) {
+            let Some(dist) = self.graph.distance(pos, t) else {
+                continue;
+            };
+            let left = left as i32 - dist - self.opening[t];
+            if left <= 0 {
+                continue;
+            }
+            let rate = self.network.rate(self.graph.valve(t));
+            let best = &mut next
+                .entry((open | 1 << t, t))
+                .or_insert_with(|| vec![0; minutes + 1])[left as usize];
+            *best = (*best).max(pressure + left as u32 * rate);
+        }
+    }
+
+    /// The most pressure which can still be released.
+    fn best(&mut self, agent: usize, pos: usize, minutes: i32, open: u64) -> (u32, Choice) {
+        let key = (agent, pos, minutes, open);
+        if let Some(&cached) = self.cache.get(&key) {
+            return cached;
+        }
+
+        let mut best = (0, Choice::Done);
+        if agent < self.last {
+            let (start, minutes) = (self.starts[agent + 1], self.agents[agent + 1].minutes);
+            best.0 = self.best(agent + 1, start, minutes, open).0;
+        }
+        for t in 0..self.targets {
+            if open & (1 << t) != 0 {
+                continue;
+            }
+            let Some(dist) = self.graph.distance(pos, t) else {
+                continue;
+            };
+            let left = minutes - dist - self.opening[t];
+            if left <= 0 {
+                continue;
+            }
+            let rate = self.network.rate(self.graph.valve(t));
+            let pressure = left as u32 * rate + self.best(agent, t, left, open | 1 << t).0;
+            if pressure > best.0 {
+                best = (pressure, Choice::Open(t));
+            }
+        }
+
+        self.cache.insert(key, best);
+        best
+    }
+
+    /// Record the moves of `agent` between two key valves, starting after `elapsed` minutes;
+    /// returns the minutes elapsed on arrival.
+    fn walk(
+        &self,
+        agent: usize,
+        mut elapsed: i32,
+        from: usize,
+        to: usize,
+        steps: &mut Vec<Step>,
+    ) -> i32 {
+        let path = self.graph.path(from, to).unwrap();
+        for hop in path.windows(2) {
+            let minutes = self
+                .network
+                .tunnels(hop[0])
+                .iter()
+                .find(|&&(n, _)| n == hop[1])
+                .unwrap()
+                .1;
+            steps.push(Step {
+                agent,
+                minute: elapsed + 1,
+                duration: minutes,
+                action: Action::Move {
+                    from: self.network.valve(hop[0]).clone(),
+                    to: self.network.valve(hop[1]).clone(),
+                },
+            });
+            elapsed += minutes;
+        }
+        elapsed
+    }
+}
+
+/// For every set, the best value of any of its subsets and which subset that is.
+fn best_of_subsets(values: Vec<u32>) -> Vec<(u32, usize)> {
+    let mut best: Vec<(u32, usize)> = values.into_iter().zip(0..).collect();
+    let mut bit = 1;
+    while bit < best.len() {
+        for mask in 0..best.len() {
+            if mask & bit != 0 && best[mask ^ bit].0 > best[mask].0 {
+                best[mask] = best[mask ^ bit];
+            }
+        }
+        bit <<= 1;
+    }
+    best
+}
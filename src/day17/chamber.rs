@@ -0,0 +1,145 @@
+use std::fmt;
+
+use super::shape::Shape;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// walls not included; at most 64
+    pub width: usize,
+    /// free columns between the left wall and a new rock
+    pub spawn_x: usize,
+    /// free rows between the top of the tower (or the floor) and a new rock
+    pub spawn_y: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            width: 7,
+            spawn_x: 2,
+            spawn_y: 3,
+        }
+    }
+}
+
+/// The tall, narrow chamber; one bit per cell, growing as the tower does.
+#[derive(Debug, Clone)]
+pub struct Chamber {
+    config: Config,
+    /// Bottom row first; bit `i` is set if column `i` (from the left) holds rock.
+    rows: Vec<u64>,
+}
+
+impl Chamber {
+    pub fn new(config: Config) -> Self {
+        assert!(
+            (1..=64).contains(&config.width),
+            "the width must be between 1 and 64"
+        );
+        Self {
+            config,
+            rows: Vec::with_capacity(4096),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Height of the tower.
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Row `y` above the floor, starting at 0.
+    pub fn row(&self, y: usize) -> u64 {
+        self.rows.get(y).copied().unwrap_or(0)
+    }
+
+    /// Let a rock fall until it comes to rest; `jets` yields `true` for a push to the right.
+    ///
+    /// Returns the position of the rock's bottom left corner.
+    pub fn drop(&mut self, shape: &Shape, mut jets: impl FnMut() -> bool) -> (usize, usize) {
+        assert!(
+            self.config.spawn_x + shape.width() <= self.config.width,
+            "rock does not fit into the chamber"
+        );
+        let (mut x, mut y) = (self.config.spawn_x, self.height() + self.config.spawn_y);
+        loop {
+            let pushed = if jets() { x + 1 } else { x.wrapping_sub(1) };
+            if !self.collides(shape, pushed, y) {
+                x = pushed;
+            }
+            if y == 0 || self.collides(shape, x, y - 1) {
+                break;
+            }
+            y -= 1;
+        }
+
+        let top = y + shape.height();
+        if self.rows.len() < top {
+            self.rows.resize(top, 0);
+        }
+        for (row, mask) in self.rows[y..top].iter_mut().zip(shape.rows()) {
+            *row |= mask << x;
+        }
+        (x, y)
+    }
+
+    /// The air cells a falling rock can still reach, row by row from the top of the tower down.
+    ///
+    /// Rocks only move sideways and down, so these cells determine how every future rock falls:
+    /// a cell next to a reachable one is either reachable itself or rock.
+    ///
+    /// Returns `None` if they reach more than `max_depth` rows down.
+    pub fn surface(&self, max_depth: usize) -> Option<Vec<u64>> {
+        let full = u64::MAX >> (64 - self.config.width);
+        let mut surface = Vec::new();
+        let mut above = full;
+        for &row in self.rows.iter().rev() {
+            let air = !row & full;
+            let mut reach = air & above;
+            loop {
+                let spread = reach | ((reach << 1 | reach >> 1) & air);
+                if spread == reach {
+                    break;
+                }
+                reach = spread;
+            }
+            if reach == 0 {
+                break;
+            }
+            if surface.len() == max_depth {
+                return None;
+            }
+            surface.push(reach);
+            above = reach;
+        }
+        Some(surface)
+    }
+
+    fn collides(&self, shape: &Shape, x: usize, y: usize) -> bool {
+        // a push to the left of column 0 wraps around
+        if x.saturating_add(shape.width()) > self.config.width {
+            return true;
+        }
+        shape
+            .rows()
+            .iter()
+            .enumerate()
+            .any(|(i, mask)| self.row(y + i) & (mask << x) != 0)
+    }
+}
+
+impl fmt::Display for Chamber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows.iter().rev() {
+            write!(f, "|")?;
+            for col in 0..self.config.width {
+                write!(f, "{}", if row & (1 << col) != 0 { '#' } else { '.' })?;
+            }
+            writeln!(f, "|")?;
+        }
+        writeln!(f, "+{}+", "-".repeat(self.config.width))
+    }
+}
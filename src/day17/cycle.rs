@@ -0,0 +1,95 @@
+use std::fmt;
+use std::hash::Hash;
+
+use ahash::AHashMap;
+use log::debug;
+
+use super::Tetris;
+
+/// Keeps the states small: a surface takes up to 8 bytes per row.
+pub const MAX_SURFACE_DEPTH: usize = 128;
+
+/// The tower repeats itself: after `start` rocks, every `period` rocks add `growth` to its
+/// height.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+    pub growth: usize,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "after {} rocks, every {} rocks add {} units",
+            self.start, self.period, self.growth
+        )
+    }
+}
+
+/// Predicts the height of the tower for any number of rocks.
+#[derive(Debug, Clone)]
+pub struct Forecast {
+    /// height after each number of rocks, up to the first repeated state
+    heights: Vec<usize>,
+    pub cycle: Cycle,
+}
+
+impl Forecast {
+    /// Drop rocks until the state repeats: the next shape, the next jet and the surface of the
+    /// tower. Equal states behave equally, so the tower grows periodically from then on.
+    ///
+    /// Returns `None` if there's no repetition within `limit` rocks, or if falling rocks can
+    /// reach deeper than [`MAX_SURFACE_DEPTH`] rows below the top, e.g. because a gap down to the
+    /// floor never closes.
+    pub fn find(mut tetris: Tetris, limit: usize) -> Option<Self> {
+        assert_eq!(0, tetris.rocks(), "expected an empty chamber");
+        let mut heights = Vec::with_capacity(4096);
+        let states = std::iter::from_fn(|| {
+            heights.push(tetris.height());
+            let state = (
+                tetris.shape_index(),
+                tetris.jets().index(),
+                tetris.chamber().surface(MAX_SURFACE_DEPTH)?,
+            );
+            tetris.drop_next();
+            Some(state)
+        });
+        let (start, period) = first_repeat(states.take(limit + 1))?;
+        let growth = heights[start + period] - heights[start];
+        let cycle = Cycle {
+            start,
+            period,
+            growth,
+        };
+        debug!("cycle: {cycle}");
+        Some(Self { heights, cycle })
+    }
+
+    /// Height of the tower after the given number of rocks.
+    pub fn height(&self, rocks: usize) -> usize {
+        if rocks < self.heights.len() {
+            return self.heights[rocks];
+        }
+        let Cycle {
+            start,
+            period,
+            growth,
+        } = self.cycle;
+        let (cycles, rem) = ((rocks - start) / period, (rocks - start) % period);
+        self.heights[start + rem] + cycles * growth
+    }
+}
+
+/// Position of the first state which occurs again, and the distance to its repetition.
+pub fn first_repeat<K: Hash + Eq>(states: impl IntoIterator<Item = K>) -> Option<(usize, usize)> {
+    let mut seen: AHashMap<K, usize> = AHashMap::with_capacity(4096);
+    for (i, state) in states.into_iter().enumerate() {
+        if let Some(&first) = seen.get(&state) {
+            return Some((first, i - first));
+        }
+        seen.insert(state, i);
+    }
+    None
+}
@@ -1,283 +1,123 @@
-use arrayvec::ArrayVec;
-use log::{debug, trace};
+pub mod chamber;
+pub mod cycle;
+pub mod shape;
 
-const MAX_ITERATIONS: usize = 4_000;
-const MAX_HEIGHT: usize = 8_000;
-const WIDTH: usize = 9;
+use log::trace;
+
+use chamber::{Chamber, Config};
+use cycle::Forecast;
+use shape::{Shape, DEFAULT_SHAPES};
+
+/// give up looking for a cycle after this many rocks
+const MAX_ROCKS: usize = 100_000;
 
 const JET_RIGHT: u8 = b'>';
 const JET_LEFT: u8 = b'<';
 
-const EMPTY: char = '.';
-
-#[derive(Debug, Clone, Copy)]
-enum Item {
-    Minus,
-    Plus,
-    ReverseL,
-    Bar,
-    Square,
+/// The jet pattern, repeated forever.
+#[derive(Debug, Clone)]
+pub struct Jets {
+    /// `true` pushes to the right
+    pattern: Vec<bool>,
+    idx: usize,
 }
 
-impl Item {
-    pub fn coords(&self, y: usize, x: usize, out: &mut ArrayVec<(usize, usize), 5>) {
-        out.clear();
-        match self {
-            Item::Minus => {
-                // y,x is the leftmost point ('#')
-                for i in x..=x + 3 {
-                    out.push((y, i));
-                }
-            }
-            Item::Plus => {
-                // y,x is the point at the bottom left ('.')
-                out.push((y, x + 1));
-                for i in x..=x + 2 {
-                    out.push((y + 1, i));
-                }
-                out.push((y + 2, x + 1));
-            }
-            Item::ReverseL => {
-                // y,x is the point at the bottom left ('#')
-                for i in x..=x + 2 {
-                    out.push((y, i));
-                }
-                for i in y + 1..=y + 2 {
-                    out.push((i, x + 2));
-                }
-            }
-            Item::Bar => {
-                // y,x is the point at the bottom ('#')
-                for i in 0..=3 {
-                    out.push((y + i, x));
-                }
-            }
-            Item::Square => {
-                // y,x is the point at the bottom left ('#')
-                for i in 0..=1 {
-                    for j in 0..=1 {
-                        out.push((y + i, x + j));
-                    }
-                }
-            }
-        }
+impl Jets {
+    /// Parse a pattern of `<` and `>`; whitespace is ignored.
+    pub fn parse(input: &[u8]) -> Self {
+        let pattern: Vec<bool> = input
+            .iter()
+            .filter(|b| !b.is_ascii_whitespace())
+            .map(|&b| match b {
+                JET_RIGHT => true,
+                JET_LEFT => false,
+                _ => panic!("unexpected jet {}", b as char),
+            })
+            .collect();
+        assert!(!pattern.is_empty(), "no jets");
+        Self { pattern, idx: 0 }
     }
-}
 
-struct Jet<'a> {
-    pattern: &'a [u8],
-    idx: usize,
-}
-
-impl<'a> Jet<'a> {
-    pub fn next(&mut self) -> u8 {
-        let result = unsafe { *self.pattern.get_unchecked(self.idx) };
+    /// The next jet; `true` if it blows to the right.
+    pub fn blow(&mut self) -> bool {
+        let result = self.pattern[self.idx];
         self.idx = (self.idx + 1) % self.pattern.len();
         result
     }
-}
 
-struct Grid {
-    /// Bottom is at y=0.
-    grid: [[char; WIDTH]; MAX_HEIGHT],
-    /// height of the tower
-    height: usize,
-    /// internal buffer
-    coords: ArrayVec<(usize, usize), 5>,
+    /// Position of the next jet in the pattern.
+    pub fn index(&self) -> usize {
+        self.idx
+    }
 }
 
-impl Grid {
-    pub fn new() -> Self {
-        let mut grid: [[char; WIDTH]; MAX_HEIGHT] = [[EMPTY; WIDTH]; MAX_HEIGHT];
-        for row in grid.iter_mut() {
-            row[0] = '|';
-            row[WIDTH - 1] = '|';
-        }
-        grid[0][0] = '+';
-        for i in 0..WIDTH {
-            grid[0][i] = '-';
-        }
-        grid[0][0] = '+';
-        grid[0][WIDTH - 1] = '+';
+/// Drops the shapes in turn into the chamber.
+#[derive(Debug, Clone)]
+pub struct Tetris {
+    shapes: Vec<Shape>,
+    jets: Jets,
+    chamber: Chamber,
+    /// number of rocks dropped so far
+    rocks: usize,
+}
 
+impl Tetris {
+    pub fn new(shapes: Vec<Shape>, jets: Jets, config: Config) -> Self {
+        assert!(!shapes.is_empty(), "no shapes");
         Self {
-            grid,
-            height: 0,
-            coords: ArrayVec::new(),
+            shapes,
+            jets,
+            chamber: Chamber::new(config),
+            rocks: 0,
         }
     }
 
-    /// Simulate the drop of a single item.
-    pub fn simulate_item(&mut self, item: Item, jet: &mut Jet) {
-        // starting position
-        let mut y: usize = self.height + 4;
-        let mut x: usize = 3;
-
-        debug!("=== dropping {:?}", item);
-
-        loop {
-            trace!(">> item at y={y}, x={x}");
-            // apply jet
-            let pattern = jet.next();
-            let new_x = if pattern == JET_RIGHT {
-                x + 1
-            } else {
-                debug_assert_eq!(JET_LEFT, pattern);
-                x - 1
-            };
-            item.coords(y, new_x, &mut self.coords);
-            if !self.has_collision(&self.coords) {
-                debug!(
-                    "Jet of gas pushes rock {}",
-                    if new_x > x { "right" } else { "left" }
-                );
-                x = new_x;
-            } else {
-                debug!(
-                    "Jet of gas pushes rock {}, but nothing happens",
-                    if new_x > x { "right" } else { "left" }
-                );
-            }
-
-            // apply gravity
-            let new_y = y - 1;
-            item.coords(new_y, x, &mut self.coords);
-            if self.has_collision(&self.coords) {
-                self.add(y, x, item, '#');
-                debug!(
-                    "Rock falls 1 unit, causing it to come to rest. height: {}",
-                    self.height
-                );
-                self.render();
-                break;
-            }
-            y = new_y;
-            debug!("Rock falls 1 unit");
-        }
+    pub fn chamber(&self) -> &Chamber {
+        &self.chamber
     }
 
-    fn has_collision(&self, coords: &[(usize, usize)]) -> bool {
-        for c in coords {
-            if self.grid[c.0][c.1] != EMPTY {
-                return true;
-            }
-        }
-        false
+    pub fn jets(&self) -> &Jets {
+        &self.jets
     }
 
-    fn add(&mut self, y: usize, x: usize, item: Item, rep: char) {
-        item.coords(y, x, &mut self.coords);
-        let mut max_y: usize = 0;
-        for c in &self.coords {
-            let y = c.0;
-            self.grid[y][c.1] = rep;
-            if y > max_y {
-                max_y = y;
-            }
-        }
-        if max_y > self.height {
-            self.height = max_y;
-        }
+    pub fn height(&self) -> usize {
+        self.chamber.height()
     }
 
-    pub fn render(&self) {
-        let rows = &self.grid[0..std::cmp::min(self.height + 7, MAX_HEIGHT)];
-        for row in rows.iter().rev() {
-            debug!("{}", row.iter().collect::<String>());
-        }
+    pub fn rocks(&self) -> usize {
+        self.rocks
     }
-}
 
-pub fn solve(input: &[u8]) -> (String, String) {
-    let mut pattern = input;
-    if pattern.last().copied() == Some(b'\n') {
-        pattern = &pattern[0..pattern.len() - 1];
+    /// Index of the shape which falls next.
+    pub fn shape_index(&self) -> usize {
+        self.rocks % self.shapes.len()
     }
-    let mut jet = Jet { pattern, idx: 0 };
-    let all_items: [Item; 5] = [
-        Item::Minus,
-        Item::Plus,
-        Item::ReverseL,
-        Item::Bar,
-        Item::Square,
-    ];
-
-    let mut grid = Grid::new();
-    let mut counter: usize = 0;
-    // deltas needed for part 2
-    let mut deltas = Vec::with_capacity(8192);
-    let mut old_height = 0;
-    let mut part1 = 0;
-    'outer: loop {
-        for &item in &all_items {
-            grid.simulate_item(item, &mut jet);
-            counter += 1;
-            if counter == 2022 {
-                part1 = grid.height;
-            }
-            let delta = grid.height - old_height;
-            deltas.push(delta);
-            old_height = grid.height;
-            if counter == MAX_ITERATIONS {
-                break 'outer;
-            }
-        }
-    }
-
-    // part 2
-    let (start, cycle_len) = find_cycle(&deltas).unwrap();
-    debug!("detected cycle of length {cycle_len} starting at {start}",);
-    let h_before: usize = deltas[0..start].iter().sum();
-    let h_cycle: usize = deltas[start..start + cycle_len].iter().sum();
-    debug!("h_before: {h_before}, h_cycle: {h_cycle}");
-
-    let total: usize = 1_000_000_000_000 - start;
-    // how many cycles do fit in
-    let cycle_count = total / cycle_len;
-    let rem = total % cycle_len;
-    debug!("cycle_count: {cycle_count}, rem: {rem}");
 
-    let h_rem: usize = deltas[start..start + rem].iter().sum();
-
-    let part2 = h_before + h_cycle * cycle_count + h_rem;
-
-    (part1.to_string(), part2.to_string())
-}
+    /// Drop the next rock; returns where it came to rest.
+    pub fn drop_next(&mut self) -> (usize, usize) {
+        let shape = &self.shapes[self.shape_index()];
+        let jets = &mut self.jets;
+        let pos = self.chamber.drop(shape, || jets.blow());
+        self.rocks += 1;
+        trace!("rock {} rests at {:?}", self.rocks, pos);
+        pos
+    }
 
-fn find_cycle(x: &[usize]) -> Option<(usize, usize)> {
-    for start in 0..x.len() / 2 {
-        if let Some(cycle_len) = find_cycle_helper(x, start) {
-            return Some((start, cycle_len));
+    /// Drop the given number of rocks.
+    pub fn run(&mut self, rocks: usize) {
+        for _ in 0..rocks {
+            self.drop_next();
         }
     }
-    None
 }
 
-/// Find cycle beginning beginning at `start`.
-fn find_cycle_helper(x: &[usize], start: usize) -> Option<usize> {
-    let x = &x[start..];
-    let n = x.len();
-    'outer: for cycle_len in 2..n / 2 {
-        let mut a1 = 0;
-        let mut b1 = cycle_len;
-        let mut a2 = cycle_len;
-        let mut b2 = 2 * cycle_len;
-        loop {
-            if b2 > n {
-                break;
-            }
-            if x[a1..b1] != x[a2..b2] {
-                debug!("cycle_len {cycle_len} invalidated: {a1}..{b1} vs {a2}..{b2}");
-                continue 'outer;
-            }
-            a1 += cycle_len;
-            b1 += cycle_len;
-            a2 += cycle_len;
-            b2 += cycle_len;
-        }
-        return Some(cycle_len);
-    }
-    None
+pub fn solve(input: &[u8]) -> (String, String) {
+    let shapes = Shape::parse_all(DEFAULT_SHAPES).unwrap();
+    let tetris = Tetris::new(shapes, Jets::parse(input), Config::default());
+    let forecast = Forecast::find(tetris, MAX_ROCKS).expect("no cycle");
+    let part1 = forecast.height(2022);
+    let part2 = forecast.height(1_000_000_000_000);
+    (part1.to_string(), part2.to_string())
 }
 
 #[cfg(test)]
@@ -296,20 +136,135 @@ mod tests {
         assert_eq!(expected, actual, "expected {expected} but got {actual}");
     }
 
+    #[test]
+    fn custom_chambers() {
+        let jets = Jets::parse(b">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>");
+        let shapes = Shape::parse_all(DEFAULT_SHAPES).unwrap();
+        assert_eq!(5, shapes.len());
+        assert_eq!(".#.\n###\n.#.\n", shapes[1].to_string());
+        assert_eq!((3, 3), (shapes[2].width(), shapes[2].height()));
+
+        let mut tetris = Tetris::new(shapes.clone(), jets.clone(), Config::default());
+        tetris.run(2);
+        assert_eq!(
+            "|...#...|\n|..###..|\n|...#...|\n|..####.|\n+-------+\n",
+            tetris.chamber().to_string()
+        );
+        tetris.run(2020);
+        assert_eq!(3068, tetris.height());
+
+        // rocks as wide as the chamber just stack up
+        let bars = Shape::parse_all("\n..###\n\n").unwrap();
+        assert_eq!("###\n", bars[0].to_string());
+        let config = Config {
+            width: 3,
+            spawn_x: 0,
+            spawn_y: 10,
+        };
+        let mut tetris = Tetris::new(bars, jets.clone(), config);
+        tetris.run(1000);
+        assert_eq!(1000, tetris.height());
+
+        // a wide chamber with a custom rock, spawning further up
+        let shapes = Shape::parse_all("#.#\n###\n\n#").unwrap();
+        let config = Config {
+            width: 40,
+            spawn_x: 17,
+            spawn_y: 5,
+        };
+        let mut tetris = Tetris::new(shapes, jets, config);
+        tetris.run(500);
+        assert!(tetris.height() < 500 * 2);
+        assert!(tetris.chamber().to_string().lines().all(|l| l.len() == 42));
+
+        assert_eq!(
+            Err(shape::ParseError {
+                line: 2,
+                reason: "expected '#' or '.'"
+            }),
+            Shape::parse_all("##\n#x\n")
+        );
+        assert_eq!(
+            Err(shape::ParseError {
+                line: 2,
+                reason: "shape without rock"
+            }),
+            Shape::parse_all("..\n..\n\n#")
+        );
+    }
+
     #[test]
     fn test_find_cycle() {
         let x = vec![1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4];
-        let result = find_cycle_helper(&x, 0);
-        assert_eq!(Some(4), result);
+        let result = cycle::first_repeat(&x);
+        assert_eq!(Some((0, 4)), result);
     }
 
     #[test]
     fn test_find_cycle_with_offset() {
         let x = vec![42, 43, 44, 1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4, 45];
-        let result = find_cycle_helper(&x, 0);
+        let result = cycle::first_repeat(&x[..7]);
         assert_eq!(None, result);
-        let result = find_cycle_helper(&x, 3);
-        assert_eq!(Some(4), result);
+        let result = cycle::first_repeat(&x);
+        assert_eq!(Some((3, 4)), result);
+    }
+
+    #[test]
+    fn forecast() {
+        let jets = Jets::parse(b">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>");
+        let shapes = Shape::parse_all(DEFAULT_SHAPES).unwrap();
+        let tetris = Tetris::new(shapes, jets.clone(), Config::default());
+        let forecast = Forecast::find(tetris.clone(), MAX_ROCKS).unwrap();
+        assert_eq!(35, forecast.cycle.period);
+        assert_eq!(53, forecast.cycle.growth);
+        assert!(forecast
+            .cycle
+            .to_string()
+            .ends_with("every 35 rocks add 53 units"));
+
+        // the extrapolation agrees with simulating every rock
+        let mut simulated = tetris;
+        for rocks in [1, 100, 2022, 3333, 5000] {
+            simulated.run(rocks - simulated.rocks());
+            assert_eq!(simulated.height(), forecast.height(rocks), "{rocks} rocks");
+        }
+
+        // single cells pushed against the right wall never close the gap on the left
+        let cells = Shape::parse_all("#").unwrap();
+        let config = Config {
+            width: 3,
+            ..Config::default()
+        };
+        let tetris = Tetris::new(cells, Jets::parse(b">"), config);
+        assert!(Forecast::find(tetris, 500).is_none());
+
+        // surfaces only keep what falling rocks can reach
+        let mut tetris = Tetris::new(
+            Shape::parse_all("###\n\n#").unwrap(),
+            Jets::parse(b"<"),
+            Config {
+                width: 3,
+                spawn_x: 0,
+                spawn_y: 1,
+            },
+        );
+        assert_eq!(Some(vec![]), tetris.chamber().surface(8));
+        tetris.run(2);
+        assert_eq!(Some(vec![0b110]), tetris.chamber().surface(8));
+        assert_eq!(None, tetris.chamber().surface(0));
+
+        // a covered hole is out of reach
+        let mut chamber = Chamber::new(Config {
+            width: 4,
+            spawn_x: 0,
+            spawn_y: 3,
+        });
+        for shape in Shape::parse_all("#.#\n\n###").unwrap() {
+            chamber.drop(&shape, || false);
+        }
+        assert_eq!(vec![0b0111, 0b0101], [chamber.row(1), chamber.row(0)]);
+        assert_eq!(Some(vec![0b1000, 0b1000]), chamber.surface(8));
+        assert_eq!(None, chamber.surface(1));
     }
 
     #[test]
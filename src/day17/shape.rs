@@ -0,0 +1,134 @@
+use std::fmt;
+
+/// The rocks of the puzzle, in the order they fall.
+pub const DEFAULT_SHAPES: &str = "####
+
+.#.
+###
+.#.
+
+..#
+..#
+###
+
+#
+#
+#
+#
+
+##
+##
+";
+
+/// A rock; row masks have bit `i` set if column `i` (from the left) is solid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shape {
+    /// bottom row first
+    rows: Vec<u64>,
+    width: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// line number, starting at 1
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl Shape {
+    /// Parse shapes drawn with `#` (rock) and `.` (air), separated by blank lines.
+    ///
+    /// Each shape is cropped to the rocks it contains.
+    pub fn parse_all(text: &str) -> Result<Vec<Shape>, ParseError> {
+        let mut shapes = Vec::new();
+        let mut block: Vec<u64> = Vec::new();
+        let mut end_of_block = |block: &mut Vec<u64>, line| -> Result<(), ParseError> {
+            if block.is_empty() {
+                return Ok(());
+            }
+            block.reverse();
+            shapes.push(Shape::from_rows(std::mem::take(block)).ok_or(ParseError {
+                line,
+                reason: "shape without rock",
+            })?);
+            Ok(())
+        };
+        for (i, line) in text.lines().map(str::trim).enumerate() {
+            if line.is_empty() {
+                end_of_block(&mut block, i)?;
+                continue;
+            }
+            let mut row = 0;
+            for (col, c) in line.chars().enumerate() {
+                match c {
+                    '#' if col < 64 => row |= 1 << col,
+                    '#' => {
+                        return Err(ParseError {
+                            line: i + 1,
+                            reason: "shape wider than 64",
+                        })
+                    }
+                    '.' => {}
+                    _ => {
+                        return Err(ParseError {
+                            line: i + 1,
+                            reason: "expected '#' or '.'",
+                        })
+                    }
+                }
+            }
+            block.push(row);
+        }
+        end_of_block(&mut block, text.lines().count())?;
+        Ok(shapes)
+    }
+
+    /// Crop the rows (bottom first) to the rocks; `None` if there are none.
+    fn from_rows(mut rows: Vec<u64>) -> Option<Shape> {
+        let all = rows.iter().fold(0, |acc, r| acc | r);
+        if all == 0 {
+            return None;
+        }
+        let first = rows.iter().position(|&r| r != 0).unwrap();
+        let last = rows.iter().rposition(|&r| r != 0).unwrap();
+        rows.truncate(last + 1);
+        rows.drain(..first);
+        let shift = all.trailing_zeros();
+        for r in rows.iter_mut() {
+            *r >>= shift;
+        }
+        let width = (u64::BITS - (all >> shift).leading_zeros()) as usize;
+        Some(Shape { rows, width })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Row masks, bottom row first.
+    pub fn rows(&self) -> &[u64] {
+        &self.rows
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows.iter().rev() {
+            for col in 0..self.width {
+                write!(f, "{}", if row & (1 << col) != 0 { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}